@@ -1,21 +1,80 @@
 use serde::Deserialize;
-use std::{fs, path::PathBuf};
+use std::{
+    collections::BTreeMap,
+    env,
+    fmt::{self, Display},
+    fs,
+    path::PathBuf,
+    process::Command,
+};
 
 use domeneshop_client::client::{DomeneshopClient, DomeneshopClientConfiguration};
 
-use crate::{constants::CREDENTIALS_FILENAME, log_and_fail_with_error, Args};
+use crate::{
+    config::{Origin, Settings},
+    constants::{
+        CREDENTIALS_FILENAME, CREDENTIALS_PROFILES_FILENAME, DEFAULT_PROFILE, KEYRING_SERVICE,
+        SECRET_ENV, TOKEN_ENV,
+    },
+    log_and_fail_with_error, Args,
+};
 
-#[derive(Deserialize)]
 pub struct ApiCredentials {
     pub token: String,
     pub secret: String,
 }
 
-pub fn get_client(args: &Args, data_dir: &PathBuf) -> Option<DomeneshopClient> {
-    let credentials = get_api_credentials(args, data_dir);
+/// Credentials as stored in `credentials.json` or a profile in `credentials.toml`. The secret is
+/// given directly, as the output of a command, or read from the OS keyring.
+#[derive(Deserialize)]
+struct StoredCredentials {
+    token: String,
+    secret: Option<String>,
+    secret_command: Option<String>,
+    #[serde(default)]
+    keyring: bool,
+}
+
+/// Contents of `credentials.toml`, holding credentials for several accounts.
+#[derive(Deserialize)]
+struct CredentialProfiles {
+    default_profile: Option<String>,
+    #[serde(default)]
+    profiles: BTreeMap<String, StoredCredentials>,
+}
+
+pub enum CredentialSource {
+    Arguments,
+    Environment,
+    Profile(String, PathBuf),
+    File(PathBuf),
+}
+
+impl Display for CredentialSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CredentialSource::Arguments => write!(f, "--token and --secret arguments"),
+            CredentialSource::Environment => {
+                write!(f, "{} and {} environment variables", TOKEN_ENV, SECRET_ENV)
+            }
+            CredentialSource::Profile(profile, file) => {
+                write!(f, "profile {} in {}", profile, file.display())
+            }
+            CredentialSource::File(file) => write!(f, "{}", file.display()),
+        }
+    }
+}
+
+pub fn get_client(
+    args: &Args,
+    settings: &Settings,
+    config_dir: &PathBuf,
+) -> Option<DomeneshopClient> {
+    let credentials = get_api_credentials(args, settings, config_dir);
     match credentials {
         None => None,
-        Some(credentials) => {
+        Some((credentials, source)) => {
+            info!("Using credentials from {}", source);
             let client = DomeneshopClient::new(
                 credentials.token,
                 credentials.secret,
@@ -33,36 +92,256 @@ pub fn get_client(args: &Args, data_dir: &PathBuf) -> Option<DomeneshopClient> {
     }
 }
 
-fn get_api_credentials(args: &Args, data_dir: &PathBuf) -> Option<ApiCredentials> {
-    match (&args.global_opts.secret, &args.global_opts.token) {
-        (Some(secret), Some(token)) => {
-            println!("Using credentials from arguments");
-            Some(ApiCredentials {
+/// Credentials are taken from the first of these that is present:
+///
+/// 1. The `--token` and `--secret` arguments
+/// 2. The `DOMENESHOP_TOKEN` and `DOMENESHOP_SECRET` environment variables, unless `--profile`
+///    was given
+/// 3. A profile in `credentials.toml` in the config directory
+/// 4. `credentials.json` in the config directory
+pub fn get_api_credentials(
+    args: &Args,
+    settings: &Settings,
+    config_dir: &PathBuf,
+) -> Option<(ApiCredentials, CredentialSource)> {
+    if let (Some(secret), Some(token)) = (&args.global_opts.secret, &args.global_opts.token) {
+        return Some((
+            ApiCredentials {
                 secret: secret.clone(),
                 token: token.clone(),
-            })
+            },
+            CredentialSource::Arguments,
+        ));
+    }
+
+    let profile_flag = matches!(
+        settings.profile.as_ref().map(|profile| &profile.origin),
+        Some(Origin::Flag)
+    );
+    if let (false, Ok(token), Ok(secret)) =
+        (profile_flag, env::var(TOKEN_ENV), env::var(SECRET_ENV))
+    {
+        return Some((
+            ApiCredentials { token, secret },
+            CredentialSource::Environment,
+        ));
+    }
+
+    let requested_profile = settings
+        .profile
+        .as_ref()
+        .map(|profile| profile.value.clone());
+    let profiles_file = config_dir.join(CREDENTIALS_PROFILES_FILENAME);
+    if profiles_file.exists() {
+        if !check_permissions(&profiles_file, args.global_opts.strict) {
+            return None;
         }
-        _ => {
-            let mut credentials = data_dir.clone();
-            credentials.push(CREDENTIALS_FILENAME);
-            let data = fs::read_to_string(credentials);
-            match data {
+        return get_profile_credentials(&profiles_file, requested_profile);
+    }
+    if let Some(profile) = requested_profile {
+        eprintln!(
+            "Profile {} was requested, but {} does not exist",
+            profile,
+            profiles_file.display()
+        );
+        return None;
+    }
+
+    let credentials_file = config_dir.join(CREDENTIALS_FILENAME);
+    if credentials_file.exists() && !check_permissions(&credentials_file, args.global_opts.strict) {
+        return None;
+    }
+    let data = fs::read_to_string(&credentials_file);
+    match data {
+        Err(err) => {
+            eprintln!("Could not find credentials-file: {}", err);
+            None
+        }
+        Ok(credentials) => {
+            let json: Result<StoredCredentials, serde_json::Error> =
+                serde_json::from_str(&credentials);
+            match json {
                 Err(err) => {
-                    println!("Could not find credentials-file: {}", err);
+                    eprintln!("Error deserializing credentials: {}", err);
                     None
                 }
-                Ok(credentials) => {
-                    let json: Result<ApiCredentials, serde_json::Error> =
-                        serde_json::from_str(&credentials);
-                    match json {
-                        Err(err) => {
-                            println!("Error deserializing credentials: {}", err);
-                            None
-                        }
-                        Ok(json) => Some(json),
-                    }
+                Ok(json) => resolve_secret(json)
+                    .map(|credentials| (credentials, CredentialSource::File(credentials_file))),
+            }
+        }
+    }
+}
+
+fn get_profile_credentials(
+    profiles_file: &PathBuf,
+    requested_profile: Option<String>,
+) -> Option<(ApiCredentials, CredentialSource)> {
+    let data = match fs::read_to_string(profiles_file) {
+        Ok(data) => data,
+        Err(err) => {
+            eprintln!("Could not read {}: {}", profiles_file.display(), err);
+            return None;
+        }
+    };
+    let mut profiles: CredentialProfiles = match toml::from_str(&data) {
+        Ok(profiles) => profiles,
+        Err(err) => {
+            eprintln!("Error deserializing credentials: {}", err);
+            return None;
+        }
+    };
+
+    let profile = requested_profile
+        .or(profiles.default_profile)
+        .unwrap_or(DEFAULT_PROFILE.to_string());
+    match profiles.profiles.remove(&profile) {
+        Some(credentials) => resolve_secret(credentials).map(|credentials| {
+            (
+                credentials,
+                CredentialSource::Profile(profile, profiles_file.clone()),
+            )
+        }),
+        None => {
+            eprintln!(
+                "Profile {} not found in {}. Available profiles: {}",
+                profile,
+                profiles_file.display(),
+                profiles
+                    .profiles
+                    .keys()
+                    .cloned()
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            );
+            None
+        }
+    }
+}
+
+/// Like ssh does with private keys, credentials files that other users can access, or that belong
+/// to another user, are warned about. With `--strict` they are not read at all.
+#[cfg(unix)]
+fn check_permissions(file: &PathBuf, strict: bool) -> bool {
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+    let metadata = match fs::metadata(file) {
+        Ok(metadata) => metadata,
+        Err(err) => {
+            warn!("Could not check permissions of {}: {}", file.display(), err);
+            return !strict;
+        }
+    };
+
+    let mut problems = Vec::new();
+    let mode = metadata.permissions().mode() & 0o777;
+    if mode & 0o077 != 0 {
+        problems.push(format!("is accessible by other users (mode {:o})", mode));
+    }
+    // SAFETY: getuid has no preconditions and cannot fail
+    let uid = unsafe { libc::getuid() };
+    if metadata.uid() != uid {
+        problems.push(format!("is owned by another user (uid {})", metadata.uid()));
+    }
+    if problems.is_empty() {
+        return true;
+    }
+
+    let problem = format!("{} {}", file.display(), problems.join(" and "));
+    if strict {
+        error!("Refusing to read credentials: {}", problem);
+        eprintln!(
+            "Refusing to read credentials: {}. Fix it with: chmod 600 {}",
+            problem,
+            file.display()
+        );
+        false
+    } else {
+        warn!("Insecure credentials: {}", problem);
+        eprintln!(
+            "Warning: {}. Fix it with: chmod 600 {}",
+            problem,
+            file.display()
+        );
+        true
+    }
+}
+
+#[cfg(not(unix))]
+fn check_permissions(_file: &PathBuf, _strict: bool) -> bool {
+    true
+}
+
+fn resolve_secret(stored: StoredCredentials) -> Option<ApiCredentials> {
+    let secret = match (&stored.secret, &stored.secret_command, stored.keyring) {
+        (Some(secret), None, false) => Some(secret.clone()),
+        (None, Some(command), false) => run_secret_command(command),
+        (None, None, true) => read_keyring(&stored.token),
+        (None, None, false) => {
+            eprintln!("Credentials need one of secret, secret_command or keyring");
+            None
+        }
+        _ => {
+            eprintln!("Credentials can only have one of secret, secret_command or keyring");
+            None
+        }
+    }?;
+
+    Some(ApiCredentials {
+        token: stored.token,
+        secret,
+    })
+}
+
+/// Runs the command through the shell and uses the first line it prints as the secret, which
+/// works with password managers like `pass show domeneshop`.
+fn run_secret_command(command: &str) -> Option<String> {
+    debug!("Running secret command '{}'", command);
+
+    let mut process = if cfg!(windows) {
+        let mut process = Command::new("cmd");
+        process.arg("/C");
+        process
+    } else {
+        let mut process = Command::new("sh");
+        process.arg("-c");
+        process
+    };
+
+    match process.arg(command).output() {
+        Ok(output) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            match stdout.lines().next().map(str::trim) {
+                Some(secret) if !secret.is_empty() => Some(secret.to_string()),
+                _ => {
+                    eprintln!("Secret command '{}' printed nothing", command);
+                    None
                 }
             }
         }
+        Ok(output) => {
+            eprintln!(
+                "Secret command '{}' exited with {}: {}",
+                command,
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+            None
+        }
+        Err(err) => {
+            eprintln!("Could not run secret command '{}': {}", command, err);
+            None
+        }
+    }
+}
+
+/// Secrets in the OS keyring (Secret Service, macOS Keychain or Windows Credential Manager) are
+/// stored per token.
+fn read_keyring(token: &str) -> Option<String> {
+    match keyring::Entry::new(KEYRING_SERVICE, token).and_then(|entry| entry.get_password()) {
+        Ok(secret) => Some(secret),
+        Err(err) => {
+            eprintln!("Could not read secret from keyring: {}", err);
+            None
+        }
     }
 }
@@ -0,0 +1,232 @@
+use std::{
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use clap::*;
+use domeneshop_client::client::{DomeneshopClient, DomeneshopClientConfiguration};
+use serde_json::json;
+
+use crate::{
+    client::{get_api_credentials, ApiCredentials},
+    config::Settings,
+    constants::{
+        CREDENTIALS_FILENAME, CREDENTIALS_PROFILES_FILENAME, DEFAULT_PROFILE, KEYRING_SERVICE,
+    },
+    log_and_fail, log_and_fail_with_error, Args,
+};
+
+#[derive(Parser)]
+pub struct AuthArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Parser)]
+pub enum Command {
+    /// Asks for a token and secret, checks them against the API and stores them
+    Login {
+        #[arg(
+            long,
+            help = "Stores the secret in the OS keyring instead of the credentials file"
+        )]
+        keyring: bool,
+    },
+    /// Shows which credentials are used, and checks that they work
+    Status,
+}
+
+pub async fn handle_auth(
+    args: &AuthArgs,
+    cli_args: &Args,
+    settings: &Settings,
+    config_dir: &PathBuf,
+) -> ExitCode {
+    match &args.command {
+        Command::Login { keyring } => login(*keyring, settings, config_dir).await,
+        Command::Status => status(cli_args, settings, config_dir).await,
+    }
+}
+
+async fn login(keyring: bool, settings: &Settings, config_dir: &PathBuf) -> ExitCode {
+    println!("Create an API token at https://domene.shop/admin?view=api");
+    let token = match read_line("Token: ") {
+        Ok(token) if !token.is_empty() => token,
+        Ok(_) => return log_and_fail("No token given"),
+        Err(err) => return log_and_fail_with_error("Could not read token", err),
+    };
+    let secret = match rpassword::prompt_password("Secret: ") {
+        Ok(secret) if !secret.trim().is_empty() => secret.trim().to_string(),
+        Ok(_) => return log_and_fail("No secret given"),
+        Err(err) => return log_and_fail_with_error("Could not read secret", err),
+    };
+
+    let credentials = ApiCredentials { token, secret };
+    if let Err(code) = verify(&credentials).await {
+        return code;
+    }
+
+    if keyring {
+        if let Err(err) = write_keyring(&credentials) {
+            return log_and_fail(err);
+        }
+        info!("Stored secret in the keyring");
+    }
+
+    let profiles_file = config_dir.join(CREDENTIALS_PROFILES_FILENAME);
+    let profile = settings
+        .profile
+        .as_ref()
+        .map(|profile| profile.value.clone());
+    let result = if profile.is_some() || profiles_file.exists() {
+        save_profile(&profiles_file, profile, &credentials, keyring)
+    } else {
+        let credentials_file = config_dir.join(CREDENTIALS_FILENAME);
+        let content = if keyring {
+            json!({ "token": credentials.token, "keyring": true })
+        } else {
+            json!({ "token": credentials.token, "secret": credentials.secret })
+        };
+        write_private(&credentials_file, &format!("{:#}\n", content))
+            .map(|_| credentials_file.display().to_string())
+    };
+
+    match result {
+        Ok(location) => {
+            info!("Stored credentials in {}", location);
+            println!("Stored credentials in {}", location);
+            ExitCode::SUCCESS
+        }
+        Err(err) => log_and_fail(err),
+    }
+}
+
+async fn status(args: &Args, settings: &Settings, config_dir: &PathBuf) -> ExitCode {
+    let Some((credentials, source)) = get_api_credentials(args, settings, config_dir) else {
+        return log_and_fail("No credentials found. Run `auth login` to store some");
+    };
+    println!("Using credentials from {}", source);
+
+    match verify(&credentials).await {
+        Ok(count) => {
+            println!("Credentials are valid, with access to {} domains", count);
+            ExitCode::SUCCESS
+        }
+        Err(code) => code,
+    }
+}
+
+/// Lists the domains, which is a cheap call that fails on wrong credentials. Returns the number
+/// of domains.
+async fn verify(credentials: &ApiCredentials) -> Result<usize, ExitCode> {
+    let client = DomeneshopClient::new(
+        credentials.token.clone(),
+        credentials.secret.clone(),
+        DomeneshopClientConfiguration::default(),
+    )
+    .map_err(|err| log_and_fail_with_error("Failed to create domeneshop client", err))?;
+
+    client
+        .list_domains()
+        .await
+        .map(|domains| domains.len())
+        .map_err(|err| log_and_fail_with_error("Credentials were rejected", err))
+}
+
+/// Adds or replaces a profile in `credentials.toml`, keeping the other profiles. Returns a
+/// description of where the credentials ended up.
+fn save_profile(
+    profiles_file: &PathBuf,
+    profile: Option<String>,
+    credentials: &ApiCredentials,
+    keyring: bool,
+) -> Result<String, String> {
+    let mut document: toml::Table = match fs::read_to_string(profiles_file) {
+        Ok(data) => toml::from_str(&data)
+            .map_err(|err| format!("Could not parse {}: {}", profiles_file.display(), err))?,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => toml::Table::new(),
+        Err(err) => {
+            return Err(format!(
+                "Could not read {}: {}",
+                profiles_file.display(),
+                err
+            ))
+        }
+    };
+
+    let profile = profile
+        .or_else(|| {
+            document
+                .get("default_profile")
+                .and_then(|profile| profile.as_str())
+                .map(String::from)
+        })
+        .unwrap_or(DEFAULT_PROFILE.to_string());
+
+    let mut entry = toml::Table::new();
+    entry.insert("token".into(), credentials.token.clone().into());
+    if keyring {
+        entry.insert("keyring".into(), true.into());
+    } else {
+        entry.insert("secret".into(), credentials.secret.clone().into());
+    }
+
+    let profiles = document
+        .entry("profiles")
+        .or_insert_with(|| toml::Table::new().into());
+    match profiles.as_table_mut() {
+        Some(profiles) => profiles.insert(profile.clone(), entry.into()),
+        None => {
+            return Err(format!(
+                "profiles in {} is not a table",
+                profiles_file.display()
+            ))
+        }
+    };
+
+    let content = toml::to_string(&document).map_err(|err| err.to_string())?;
+    write_private(profiles_file, &content)?;
+    Ok(format!(
+        "profile {} in {}",
+        profile,
+        profiles_file.display()
+    ))
+}
+
+/// Stores the secret under the token, where `keyring = true` in the credentials file reads it.
+fn write_keyring(credentials: &ApiCredentials) -> Result<(), String> {
+    keyring::Entry::new(KEYRING_SERVICE, &credentials.token)
+        .and_then(|entry| entry.set_password(&credentials.secret))
+        .map_err(|err| format!("Could not store secret in keyring: {}", err))
+}
+
+/// Writes a file only the owner can read. Permissions of an existing file are tightened as well.
+fn write_private(file: &PathBuf, content: &str) -> Result<(), String> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        if file.exists() {
+            fs::set_permissions(file, fs::Permissions::from_mode(0o600)).map_err(|err| {
+                format!("Could not set permissions on {}: {}", file.display(), err)
+            })?;
+        }
+    }
+
+    options
+        .open(file)
+        .and_then(|mut handle| handle.write_all(content.as_bytes()))
+        .map_err(|err| format!("Could not write {}: {}", file.display(), err))
+}
+
+fn read_line(prompt: &str) -> io::Result<String> {
+    print!("{}", prompt);
+    io::stdout().flush()?;
+    let mut line = String::new();
+    io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
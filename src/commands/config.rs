@@ -0,0 +1,90 @@
+use std::{fmt::Display, process::ExitCode};
+
+use clap::*;
+use serde_json::{json, Value};
+
+use crate::{
+    config::{value_name, Setting, Settings},
+    output::{Output, Render},
+};
+
+#[derive(Parser)]
+pub struct ConfigArgs {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Parser)]
+pub enum Command {
+    /// Shows the effective settings and where each one came from
+    Show,
+}
+
+struct SettingRow {
+    name: &'static str,
+    value: Option<String>,
+    origin: String,
+}
+
+impl Render for SettingRow {
+    const DEFAULT_COLUMNS: &'static [&'static str] = &["name", "value", "origin"];
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("name", json!(self.name)),
+            ("value", json!(self.value)),
+            ("origin", json!(self.origin)),
+        ]
+    }
+}
+
+pub fn handle_config(args: &ConfigArgs, settings: &Settings, output: &Output) -> ExitCode {
+    match &args.command {
+        Command::Show => output.list(&rows(settings)),
+    }
+}
+
+fn rows(settings: &Settings) -> Vec<SettingRow> {
+    vec![
+        row("config", Some(&settings.file), |file| {
+            file.display().to_string()
+        }),
+        row("data_directory", settings.data_directory.as_ref(), |dir| {
+            dir.display().to_string()
+        }),
+        row("log_directory", settings.log_directory.as_ref(), |dir| {
+            dir.display().to_string()
+        }),
+        row("profile", settings.profile.as_ref(), to_string),
+        row("output", Some(&settings.output), value_name),
+        row("log_level", Some(&settings.log_level), value_name),
+        row("domain", settings.domain.as_ref(), to_string),
+        row("dyndns.hosts", settings.dyndns_hosts.as_ref(), |hosts| {
+            hosts.join(",")
+        }),
+    ]
+}
+
+/// Settings that are not set anywhere show as empty, coming from the default.
+fn row<T>(
+    name: &'static str,
+    setting: Option<&Setting<T>>,
+    format: impl Fn(&T) -> String,
+) -> SettingRow {
+    match setting {
+        Some(setting) => SettingRow {
+            name,
+            value: Some(format(&setting.value)),
+            origin: setting.origin.to_string(),
+        },
+        None => SettingRow {
+            name,
+            value: None,
+            origin: "default".to_string(),
+        },
+    }
+}
+
+fn to_string<T: Display>(value: &T) -> String {
+    value.to_string()
+}
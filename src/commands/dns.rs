@@ -1,23 +1,38 @@
-use std::process::ExitCode;
+mod sync;
+mod zonefile;
+
+use std::{path::PathBuf, process::ExitCode};
 
 use clap::*;
+use clap_complete::ArgValueCandidates;
 use domeneshop_client::{
     client::DomeneshopClient,
     endpoints::{
-        dns::{DnsId, DnsType, ExistingDnsRecord},
+        dns::{DnsId, DnsRecord, DnsType, ExistingDnsRecord},
         domains::DomainId,
     },
 };
+use serde::Deserialize;
+use serde_json::{json, Value};
 
 use crate::{
+    completion::{cache_dns_records, dns_id_candidates, domain_candidates},
+    constants::DEFAULT_DNS_TTL,
     domain_lookup::{get_domain_id, DomainIdOrHost},
     log_and_fail, log_and_fail_with_error,
+    output::{Output, Render},
+    prompt::{can_prompt, confirm},
 };
 
 #[derive(Parser)]
 pub struct DnsArgs {
-    #[arg(short, long, help = "Id or name of the domain to manage DNS for")]
-    domain: DomainIdOrHost,
+    #[arg(
+        short,
+        long,
+        add = ArgValueCandidates::new(domain_candidates),
+        help = "Id or name of the domain to manage DNS for. Defaults to domain in config.toml"
+    )]
+    domain: Option<DomainIdOrHost>,
     #[command(subcommand)]
     command: Command,
 }
@@ -26,6 +41,12 @@ pub struct DnsArgs {
 pub enum Command {
     List(ListDnsArgs),
     Get(GetDnsArgs),
+    Add(AddDnsArgs),
+    Update(UpdateDnsArgs),
+    Delete(DeleteDnsArgs),
+    Sync(sync::SyncDnsArgs),
+    Export(zonefile::ExportDnsArgs),
+    Import(zonefile::ImportDnsArgs),
 }
 
 #[derive(Parser)]
@@ -38,10 +59,62 @@ pub struct ListDnsArgs {
 
 #[derive(Parser)]
 pub struct GetDnsArgs {
+    #[arg(add = ArgValueCandidates::new(dns_id_candidates))]
+    id: DnsId,
+}
+
+#[derive(Parser)]
+pub struct AddDnsArgs {
+    #[arg(long, help = "Host of the record. Use @ for the domain itself")]
+    host: String,
+    #[arg(long, help = "Type of the record")]
+    r#type: DnsTypeArg,
+    #[arg(long, help = "Data of the record, e.g. an ip address or a hostname")]
+    data: String,
+    #[arg(long, default_value_t = DEFAULT_DNS_TTL, help = "Time to live in seconds")]
+    ttl: i32,
+    #[arg(long, help = "Priority of the record. Required for MX and SRV")]
+    priority: Option<i32>,
+    #[arg(long, help = "Weight of the record. Required for SRV")]
+    weight: Option<i32>,
+    #[arg(long, help = "Port of the record. Required for SRV")]
+    port: Option<i32>,
+}
+
+#[derive(Parser)]
+pub struct UpdateDnsArgs {
+    #[arg(add = ArgValueCandidates::new(dns_id_candidates))]
+    id: DnsId,
+    #[arg(long, help = "New host of the record")]
+    host: Option<String>,
+    #[arg(long, help = "New type of the record")]
+    r#type: Option<DnsTypeArg>,
+    #[arg(long, help = "New data of the record")]
+    data: Option<String>,
+    #[arg(long, help = "New time to live in seconds")]
+    ttl: Option<i32>,
+    #[arg(long, help = "New priority of the record")]
+    priority: Option<i32>,
+    #[arg(long, help = "New weight of the record")]
+    weight: Option<i32>,
+    #[arg(long, help = "New port of the record")]
+    port: Option<i32>,
+}
+
+#[derive(Parser)]
+pub struct DeleteDnsArgs {
+    #[arg(add = ArgValueCandidates::new(dns_id_candidates))]
     id: DnsId,
+    #[arg(
+        short,
+        long,
+        action,
+        help = "Deletes the record without asking for confirmation"
+    )]
+    yes: bool,
 }
 
-#[derive(ValueEnum, Clone, Debug)]
+#[derive(ValueEnum, Deserialize, Clone, Debug, PartialEq)]
 enum DnsTypeArg {
     A,
     AAAA,
@@ -51,29 +124,52 @@ enum DnsTypeArg {
     TXT,
 }
 
-pub async fn handle_dns(args: &DnsArgs, client: &DomeneshopClient) -> ExitCode {
-    match get_domain_id(&args.domain, client).await {
+pub async fn handle_dns(
+    args: &DnsArgs,
+    client: &DomeneshopClient,
+    default_domain: Option<&DomainIdOrHost>,
+    cache_dir: &PathBuf,
+    output: &Output,
+) -> ExitCode {
+    let Some(domain) = args.domain.as_ref().or(default_domain) else {
+        return log_and_fail("No domain given. Use --domain or set domain in config.toml");
+    };
+    match get_domain_id(domain, client).await {
         Some(domain_id) => match &args.command {
-            Command::List(args) => list_dns(client, domain_id, args).await,
-            Command::Get(args) => get_dns(client, domain_id, args.id).await,
+            Command::List(args) => list_dns(client, domain_id, args, cache_dir, output).await,
+            Command::Get(args) => get_dns(client, domain_id, args.id, output).await,
+            Command::Add(args) => add_dns(client, domain_id, args).await,
+            Command::Update(args) => update_dns(client, domain_id, args).await,
+            Command::Delete(args) => delete_dns(client, domain_id, args, output).await,
+            Command::Sync(args) => sync::sync_dns(client, domain_id, args).await,
+            Command::Export(args) => zonefile::export_dns(client, domain_id, args).await,
+            Command::Import(args) => zonefile::import_dns(client, domain_id, args).await,
         },
         None => log_and_fail("Could not resolve --domain input to a domain"),
     }
 }
 
-async fn get_dns(client: &DomeneshopClient, domain_id: DomainId, id: DnsId) -> ExitCode {
+async fn get_dns(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    id: DnsId,
+    output: &Output,
+) -> ExitCode {
     info!("Getting dns with id {} for domain {}", id, domain_id);
 
     match client.get_dns_record(domain_id, id).await {
-        Ok(dns) => {
-            print_dns(dns);
-            ExitCode::SUCCESS
-        }
+        Ok(dns) => output.item(&dns),
         Err(err) => log_and_fail_with_error("Failed to get dns", err),
     }
 }
 
-async fn list_dns(client: &DomeneshopClient, domain_id: DomainId, args: &ListDnsArgs) -> ExitCode {
+async fn list_dns(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &ListDnsArgs,
+    cache_dir: &PathBuf,
+    output: &Output,
+) -> ExitCode {
     info!(
         "Listing dns with host {:?} and type {:?} ...",
         args.host, args.r#type
@@ -88,12 +184,146 @@ async fn list_dns(client: &DomeneshopClient, domain_id: DomainId, args: &ListDns
     match response {
         Err(err) => log_and_fail_with_error("Failed to list dns", err),
         Ok(dns_records) => {
-            println!("Got {} dns records.", dns_records.len());
-            for record in dns_records {
-                println!("{}: {:?}", record.id, record.data);
+            info!("Got {} dns records", dns_records.len());
+            if args.host.is_none() && args.r#type.is_none() {
+                cache_dns_records(cache_dir, domain_id, &dns_records);
             }
+            output.list(&dns_records)
+        }
+    }
+}
+
+async fn add_dns(client: &DomeneshopClient, domain_id: DomainId, args: &AddDnsArgs) -> ExitCode {
+    info!(
+        "Adding {:?} record for host {} to domain {}",
+        args.r#type, args.host, domain_id
+    );
+
+    let record = DnsRecord {
+        host: args.host.clone(),
+        ttl: args.ttl,
+        r#type: map_dns_type(args.r#type.clone()),
+        data: args.data.clone(),
+        priority: args.priority,
+        weight: args.weight,
+        port: args.port,
+    };
+
+    if let Err(err) = validate_type_specific_fields(&record) {
+        return log_and_fail(err);
+    }
+
+    match client.add_dns_record(domain_id, record).await {
+        Ok(id) => {
+            info!("Added dns record with id {}", id);
+            println!("Added dns record with id {}", id);
             ExitCode::SUCCESS
         }
+        Err(err) => log_and_fail_with_error("Failed to add dns", err),
+    }
+}
+
+async fn update_dns(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &UpdateDnsArgs,
+) -> ExitCode {
+    info!("Updating dns with id {} for domain {}", args.id, domain_id);
+
+    let existing = match client.get_dns_record(domain_id, args.id).await {
+        Ok(dns) => dns,
+        Err(err) => return log_and_fail_with_error("Failed to get dns", err),
+    };
+
+    let record = merge_dns_record(existing.data, args);
+    if let Err(err) = validate_type_specific_fields(&record) {
+        return log_and_fail(err);
+    }
+
+    match client.update_dns_record(domain_id, args.id, record).await {
+        Ok(_) => {
+            info!("Updated dns record with id {}", args.id);
+            println!("Updated dns record with id {}", args.id);
+            ExitCode::SUCCESS
+        }
+        Err(err) => log_and_fail_with_error("Failed to update dns", err),
+    }
+}
+
+async fn delete_dns(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &DeleteDnsArgs,
+    output: &Output,
+) -> ExitCode {
+    info!("Deleting dns with id {} for domain {}", args.id, domain_id);
+
+    if !args.yes {
+        if !can_prompt() {
+            return log_and_fail("Not deleting without confirmation. Use --yes to delete anyway");
+        }
+        let existing = match client.get_dns_record(domain_id, args.id).await {
+            Ok(dns) => dns,
+            Err(err) => return log_and_fail_with_error("Failed to get dns", err),
+        };
+        let shown = output.item(&existing);
+        if shown != ExitCode::SUCCESS {
+            return shown;
+        }
+        if !confirm("Delete this record?") {
+            return log_and_fail("Aborted, the record was not deleted");
+        }
+    }
+
+    match client.delete_dns_record(domain_id, args.id).await {
+        Ok(_) => {
+            info!("Deleted dns record with id {}", args.id);
+            println!("Deleted dns record with id {}", args.id);
+            ExitCode::SUCCESS
+        }
+        Err(err) => log_and_fail_with_error("Failed to delete dns", err),
+    }
+}
+
+/// Changing the type drops the priority, weight and port the new type has no use for, so an MX
+/// turned into an A record doesn't keep its priority.
+fn merge_dns_record(existing: DnsRecord, args: &UpdateDnsArgs) -> DnsRecord {
+    let type_changed = args
+        .r#type
+        .as_ref()
+        .is_some_and(|new| dns_type_arg(&existing.r#type).as_ref() != Some(new));
+    let r#type = args
+        .r#type
+        .clone()
+        .map(map_dns_type)
+        .unwrap_or(existing.r#type);
+    let (uses_priority, uses_weight_and_port) = match r#type {
+        DnsType::MX => (true, false),
+        DnsType::SRV => (true, true),
+        _ => (false, false),
+    };
+    let keep = |used: bool, value: Option<i32>| value.filter(|_| used || !type_changed);
+
+    DnsRecord {
+        host: args.host.clone().unwrap_or(existing.host),
+        ttl: args.ttl.unwrap_or(existing.ttl),
+        data: args.data.clone().unwrap_or(existing.data),
+        priority: keep(uses_priority, args.priority.or(existing.priority)),
+        weight: keep(uses_weight_and_port, args.weight.or(existing.weight)),
+        port: keep(uses_weight_and_port, args.port.or(existing.port)),
+        r#type,
+    }
+}
+
+fn validate_type_specific_fields(record: &DnsRecord) -> Result<(), &'static str> {
+    match record.r#type {
+        DnsType::MX if record.priority.is_none() => Err("MX records require --priority"),
+        DnsType::SRV
+            if record.priority.is_none() || record.weight.is_none() || record.port.is_none() =>
+        {
+            Err("SRV records require --priority, --weight and --port")
+        }
+        _ => Ok(()),
     }
 }
 
@@ -108,6 +338,85 @@ fn map_dns_type(dns_type: DnsTypeArg) -> DnsType {
     }
 }
 
-fn print_dns(dns: ExistingDnsRecord) {
-    println!("{:?}", dns);
+/// The reverse of `map_dns_type`. Types that can't be given as arguments map to `None`.
+fn dns_type_arg(dns_type: &DnsType) -> Option<DnsTypeArg> {
+    match dns_type {
+        DnsType::A => Some(DnsTypeArg::A),
+        DnsType::AAAA => Some(DnsTypeArg::AAAA),
+        DnsType::CNAME => Some(DnsTypeArg::CNAME),
+        DnsType::MX => Some(DnsTypeArg::MX),
+        DnsType::SRV => Some(DnsTypeArg::SRV),
+        DnsType::TXT => Some(DnsTypeArg::TXT),
+        _ => None,
+    }
+}
+
+impl Render for ExistingDnsRecord {
+    const DEFAULT_COLUMNS: &'static [&'static str] = &["id", "host", "type", "ttl", "data"];
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("id", json!(self.id)),
+            ("host", json!(self.data.host)),
+            ("type", json!(format!("{:?}", self.data.r#type))),
+            ("data", json!(self.data.data)),
+            ("ttl", json!(self.data.ttl)),
+            ("priority", json!(self.data.priority)),
+            ("weight", json!(self.data.weight)),
+            ("port", json!(self.data.port)),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mx_record() -> DnsRecord {
+        DnsRecord {
+            host: "@".to_string(),
+            ttl: 3600,
+            r#type: DnsType::MX,
+            data: "mail.example.com".to_string(),
+            priority: Some(10),
+            weight: None,
+            port: None,
+        }
+    }
+
+    fn update(args: &[&str]) -> UpdateDnsArgs {
+        UpdateDnsArgs::parse_from(["update", "1"].iter().chain(args))
+    }
+
+    #[test]
+    fn keeps_priority_when_type_is_unchanged() {
+        let merged = merge_dns_record(mx_record(), &update(&["--type", "mx", "--ttl", "60"]));
+
+        assert_eq!(merged.priority, Some(10));
+        assert_eq!(merged.ttl, 60);
+    }
+
+    #[test]
+    fn clears_fields_the_new_type_does_not_use() {
+        let merged = merge_dns_record(
+            mx_record(),
+            &update(&["--type", "a", "--data", "192.0.2.1"]),
+        );
+
+        assert!(matches!(merged.r#type, DnsType::A));
+        assert_eq!(merged.priority, None);
+    }
+
+    #[test]
+    fn keeps_fields_the_new_type_uses() {
+        let merged = merge_dns_record(
+            mx_record(),
+            &update(&["--type", "srv", "--weight", "5", "--port", "25"]),
+        );
+
+        assert_eq!(
+            (merged.priority, merged.weight, merged.port),
+            (Some(10), Some(5), Some(25))
+        );
+    }
 }
@@ -0,0 +1,456 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use clap::Parser;
+use domeneshop_client::{
+    client::DomeneshopClient,
+    endpoints::{
+        dns::{DnsId, DnsRecord, ExistingDnsRecord},
+        domains::DomainId,
+    },
+};
+use serde::Deserialize;
+
+use super::{dns_type_arg, map_dns_type, validate_type_specific_fields, DnsTypeArg};
+use crate::{
+    constants::DEFAULT_DNS_TTL,
+    log_and_fail, log_and_fail_with_error,
+    prompt::{can_prompt, confirm},
+};
+
+#[derive(Parser)]
+pub struct SyncDnsArgs {
+    #[arg(
+        long,
+        help = "TOML or YAML (.yaml, .yml) file describing the desired records of the domain"
+    )]
+    file: PathBuf,
+    #[arg(
+        long,
+        action,
+        help = "Prints the planned changes without applying them"
+    )]
+    dry_run: bool,
+    #[arg(
+        short,
+        long,
+        action,
+        help = "Applies changes that delete records without asking for confirmation"
+    )]
+    yes: bool,
+    #[arg(
+        long,
+        action,
+        help = "Accepts a file without records, which deletes all records of the supported types"
+    )]
+    allow_empty: bool,
+}
+
+/// Unknown fields are rejected, so a misspelled `records` can't turn into an empty file that
+/// deletes every record.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ZoneFile {
+    #[serde(default)]
+    records: Vec<ZoneRecord>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ZoneRecord {
+    host: String,
+    r#type: DnsTypeArg,
+    data: String,
+    #[serde(default = "default_ttl")]
+    ttl: i32,
+    priority: Option<i32>,
+    weight: Option<i32>,
+    port: Option<i32>,
+}
+
+enum Change {
+    Create(DnsRecord),
+    Update {
+        id: DnsId,
+        from: DnsRecord,
+        to: DnsRecord,
+    },
+    Delete(ExistingDnsRecord),
+}
+
+fn default_ttl() -> i32 {
+    DEFAULT_DNS_TTL
+}
+
+pub async fn sync_dns(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &SyncDnsArgs,
+) -> ExitCode {
+    info!(
+        "Syncing dns for domain {} from {} ...",
+        domain_id,
+        args.file.display()
+    );
+
+    let desired = match read_zone_file(&args.file) {
+        Ok(records) => records,
+        Err(err) => return log_and_fail(err),
+    };
+    if desired.is_empty() && !args.allow_empty {
+        return log_and_fail(format!(
+            "{} has no records, so syncing would delete all records. Use --allow-empty if that is intended",
+            args.file.display()
+        ));
+    }
+    for record in &desired {
+        if let Err(err) = validate_type_specific_fields(record) {
+            return log_and_fail(format!("Invalid record {}: {}", describe(record), err));
+        }
+    }
+
+    let current = match client
+        .list_dns_records_with_filter(domain_id, None, None)
+        .await
+    {
+        Ok(records) => records,
+        Err(err) => return log_and_fail_with_error("Failed to list dns", err),
+    };
+
+    let (current, unsupported) = partition_supported(current);
+    for existing in &unsupported {
+        println!(
+            "= {}: {} (type not supported in sync files, left as is)",
+            existing.id,
+            describe(&existing.data)
+        );
+    }
+
+    let changes = plan_changes(current, desired);
+    if changes.is_empty() {
+        println!("Records are already in sync");
+        return ExitCode::SUCCESS;
+    }
+
+    for change in &changes {
+        print_change(change);
+    }
+
+    if args.dry_run {
+        println!("Dry run, {} changes not applied", changes.len());
+        return ExitCode::SUCCESS;
+    }
+
+    let deletes = changes
+        .iter()
+        .filter(|change| matches!(change, Change::Delete(_)))
+        .count();
+    if deletes > 0 && !args.yes {
+        if !can_prompt() {
+            return log_and_fail(format!(
+                "Not deleting {} records without confirmation. Use --yes to apply the changes anyway",
+                deletes
+            ));
+        }
+        if !confirm(&format!(
+            "Apply these changes, deleting {} records?",
+            deletes
+        )) {
+            return log_and_fail("Aborted, no changes were applied");
+        }
+    }
+
+    apply_changes(client, domain_id, changes).await
+}
+
+/// The format is chosen by extension: `.yaml` and `.yml` are read as YAML, anything else as TOML.
+fn read_zone_file(file: &PathBuf) -> Result<Vec<DnsRecord>, String> {
+    let content = fs::read_to_string(file)
+        .map_err(|err| format!("Could not read {}: {}", file.display(), err))?;
+    let is_yaml = file
+        .extension()
+        .is_some_and(|extension| extension == "yaml" || extension == "yml");
+    let zone: ZoneFile = if is_yaml {
+        serde_yaml::from_str(&content).map_err(|err| err.to_string())
+    } else {
+        toml::from_str(&content).map_err(|err| err.to_string())
+    }
+    .map_err(|err| format!("Could not parse {}: {}", file.display(), err))?;
+
+    Ok(zone
+        .records
+        .into_iter()
+        .map(|record| DnsRecord {
+            host: record.host,
+            ttl: record.ttl,
+            r#type: map_dns_type(record.r#type),
+            data: record.data,
+            priority: record.priority,
+            weight: record.weight,
+            port: record.port,
+        })
+        .collect())
+}
+
+/// Pairs desired records with existing ones. Exact matches on host, type and data are kept or
+/// updated in place, remaining records with the same host and type are updated to the new data,
+/// and whatever is left over is created or deleted. `current` must only hold records of types
+/// the file can describe.
+fn plan_changes(current: Vec<ExistingDnsRecord>, desired: Vec<DnsRecord>) -> Vec<Change> {
+    let mut current: Vec<Option<ExistingDnsRecord>> = current.into_iter().map(Some).collect();
+    let mut unmatched = Vec::new();
+    let mut changes = Vec::new();
+
+    for record in desired {
+        let position = current.iter().position(|existing| {
+            existing
+                .as_ref()
+                .is_some_and(|existing| same_record(&existing.data, &record))
+        });
+        match position.and_then(|i| current[i].take()) {
+            Some(existing) if same_settings(&existing.data, &record) => (),
+            Some(existing) => changes.push(Change::Update {
+                id: existing.id,
+                from: existing.data,
+                to: record,
+            }),
+            None => unmatched.push(record),
+        }
+    }
+
+    for record in unmatched {
+        let position = current.iter().position(|existing| {
+            existing
+                .as_ref()
+                .is_some_and(|existing| same_host_and_type(&existing.data, &record))
+        });
+        match position.and_then(|i| current[i].take()) {
+            Some(existing) => changes.push(Change::Update {
+                id: existing.id,
+                from: existing.data,
+                to: record,
+            }),
+            None => changes.push(Change::Create(record)),
+        }
+    }
+
+    changes.extend(current.into_iter().flatten().map(Change::Delete));
+    changes
+}
+
+async fn apply_changes(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    changes: Vec<Change>,
+) -> ExitCode {
+    let mut failures = 0;
+
+    for change in changes {
+        let result = match change {
+            Change::Create(record) => client
+                .add_dns_record(domain_id, record)
+                .await
+                .map(|id| info!("Created dns record with id {}", id)),
+            Change::Update { id, to, .. } => client
+                .update_dns_record(domain_id, id, to)
+                .await
+                .map(|_| info!("Updated dns record with id {}", id)),
+            Change::Delete(existing) => client
+                .delete_dns_record(domain_id, existing.id)
+                .await
+                .map(|_| info!("Deleted dns record with id {}", existing.id)),
+        };
+
+        if let Err(err) = result {
+            failures += 1;
+            error!("Failed to apply change: {:?}", err);
+            eprintln!("Failed to apply change: {}", err);
+        }
+    }
+
+    if failures > 0 {
+        log_and_fail(format!("{} changes failed", failures))
+    } else {
+        println!("Records are in sync");
+        ExitCode::SUCCESS
+    }
+}
+
+/// Splits off records the file can't describe, like NS or CAA, which a sync never deletes.
+fn partition_supported(
+    current: Vec<ExistingDnsRecord>,
+) -> (Vec<ExistingDnsRecord>, Vec<ExistingDnsRecord>) {
+    current
+        .into_iter()
+        .partition(|existing| dns_type_arg(&existing.data.r#type).is_some())
+}
+
+fn same_host_and_type(existing: &DnsRecord, desired: &DnsRecord) -> bool {
+    existing.host.eq_ignore_ascii_case(&desired.host)
+        && matches!(
+            (dns_type_arg(&existing.r#type), dns_type_arg(&desired.r#type)),
+            (Some(existing), Some(desired)) if existing == desired
+        )
+}
+
+pub(super) fn same_record(existing: &DnsRecord, desired: &DnsRecord) -> bool {
+    same_host_and_type(existing, desired) && existing.data == desired.data
+}
+
+fn same_settings(existing: &DnsRecord, desired: &DnsRecord) -> bool {
+    existing.ttl == desired.ttl
+        && existing.priority == desired.priority
+        && existing.weight == desired.weight
+        && existing.port == desired.port
+}
+
+pub(super) fn describe(record: &DnsRecord) -> String {
+    let mut description = format!(
+        "{} {:?} {} (ttl {})",
+        record.host, record.r#type, record.data, record.ttl
+    );
+    if let Some(priority) = record.priority {
+        description.push_str(&format!(" priority {}", priority));
+    }
+    if let Some(weight) = record.weight {
+        description.push_str(&format!(" weight {}", weight));
+    }
+    if let Some(port) = record.port {
+        description.push_str(&format!(" port {}", port));
+    }
+    description
+}
+
+fn print_change(change: &Change) {
+    match change {
+        Change::Create(record) => println!("+ {}", describe(record)),
+        Change::Update { id, from, to } => {
+            println!("~ {}: {} -> {}", id, describe(from), describe(to))
+        }
+        Change::Delete(existing) => println!("- {}: {}", existing.id, describe(&existing.data)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domeneshop_client::endpoints::dns::DnsType;
+
+    use super::*;
+
+    fn record(host: &str, r#type: DnsType, data: &str, ttl: i32) -> DnsRecord {
+        DnsRecord {
+            host: host.to_string(),
+            ttl,
+            r#type,
+            data: data.to_string(),
+            priority: None,
+            weight: None,
+            port: None,
+        }
+    }
+
+    fn existing(id: DnsId, data: DnsRecord) -> ExistingDnsRecord {
+        ExistingDnsRecord { id, data }
+    }
+
+    fn summary(changes: &[Change]) -> Vec<String> {
+        changes
+            .iter()
+            .map(|change| match change {
+                Change::Create(record) => format!("+ {}", describe(record)),
+                Change::Update { id, to, .. } => format!("~ {}: {}", id, describe(to)),
+                Change::Delete(existing) => format!("- {}", existing.id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_records_in_sync() {
+        let current = vec![
+            existing(1, record("@", DnsType::A, "192.0.2.1", 3600)),
+            existing(2, record("www", DnsType::CNAME, "example.com", 3600)),
+        ];
+        let desired = vec![
+            record("www", DnsType::CNAME, "example.com", 3600),
+            record("@", DnsType::A, "192.0.2.1", 3600),
+        ];
+
+        assert!(plan_changes(current, desired).is_empty());
+    }
+
+    #[test]
+    fn updates_ttl_in_place() {
+        let current = vec![existing(1, record("@", DnsType::A, "192.0.2.1", 3600))];
+        let desired = vec![record("@", DnsType::A, "192.0.2.1", 300)];
+
+        assert_eq!(
+            summary(&plan_changes(current, desired)),
+            ["~ 1: @ A 192.0.2.1 (ttl 300)"]
+        );
+    }
+
+    #[test]
+    fn updates_data_of_same_host_and_type() {
+        let current = vec![
+            existing(1, record("@", DnsType::A, "192.0.2.1", 3600)),
+            existing(2, record("@", DnsType::A, "192.0.2.2", 3600)),
+        ];
+        let desired = vec![
+            record("@", DnsType::A, "192.0.2.2", 3600),
+            record("@", DnsType::A, "192.0.2.3", 3600),
+        ];
+
+        assert_eq!(
+            summary(&plan_changes(current, desired)),
+            ["~ 1: @ A 192.0.2.3 (ttl 3600)"]
+        );
+    }
+
+    #[test]
+    fn creates_missing_records() {
+        let current = vec![existing(1, record("@", DnsType::A, "192.0.2.1", 3600))];
+        let desired = vec![
+            record("@", DnsType::A, "192.0.2.1", 3600),
+            record("@", DnsType::AAAA, "2001:db8::1", 3600),
+        ];
+
+        assert_eq!(
+            summary(&plan_changes(current, desired)),
+            ["+ @ AAAA 2001:db8::1 (ttl 3600)"]
+        );
+    }
+
+    #[test]
+    fn deletes_records_not_in_file() {
+        let current = vec![
+            existing(1, record("@", DnsType::A, "192.0.2.1", 3600)),
+            existing(2, record("old", DnsType::TXT, "gone", 3600)),
+        ];
+        let desired = vec![record("@", DnsType::A, "192.0.2.1", 3600)];
+
+        assert_eq!(summary(&plan_changes(current, desired)), ["- 2"]);
+    }
+
+    #[test]
+    fn never_plans_changes_to_unsupported_types() {
+        let current = vec![
+            existing(1, record("@", DnsType::A, "192.0.2.1", 3600)),
+            existing(2, record("@", DnsType::NS, "ns1.example.com", 3600)),
+            existing(3, record("@", DnsType::CAA, "0 issue \"ca.example\"", 3600)),
+        ];
+
+        let (supported, unsupported) = partition_supported(current);
+        let unsupported: Vec<DnsId> = unsupported.iter().map(|existing| existing.id).collect();
+        assert_eq!(unsupported, [2, 3]);
+
+        assert_eq!(summary(&plan_changes(supported, Vec::new())), ["- 1"]);
+    }
+
+    #[test]
+    fn rejects_unknown_keys() {
+        let file = "[[record]]\nhost = \"@\"\ntype = \"A\"\ndata = \"192.0.2.1\"\n";
+        assert!(toml::from_str::<ZoneFile>(file).is_err());
+
+        let file = "[[records]]\nhost = \"@\"\ntype = \"A\"\ndata = \"192.0.2.1\"\nttl_ = 1\n";
+        assert!(toml::from_str::<ZoneFile>(file).is_err());
+    }
+}
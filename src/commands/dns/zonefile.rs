@@ -0,0 +1,691 @@
+use std::{fs, path::PathBuf, process::ExitCode};
+
+use clap::{Parser, ValueEnum};
+use domeneshop_client::{
+    client::DomeneshopClient,
+    endpoints::{
+        dns::{DnsRecord, DnsType, ExistingDnsRecord},
+        domains::DomainId,
+    },
+};
+
+use super::{
+    map_dns_type,
+    sync::{describe, same_record},
+    validate_type_specific_fields, DnsTypeArg,
+};
+use crate::{constants::DEFAULT_DNS_TTL, log_and_fail, log_and_fail_with_error};
+
+/// Types that are valid in zone files but can't be managed through the API. Records of these
+/// types are skipped, while any other unknown token in the type position is an error.
+const UNSUPPORTED_TYPES: &[&str] = &[
+    "SOA",
+    "NS",
+    "PTR",
+    "CAA",
+    "DS",
+    "DNSKEY",
+    "CDS",
+    "CDNSKEY",
+    "RRSIG",
+    "NSEC",
+    "NSEC3",
+    "NSEC3PARAM",
+    "TLSA",
+    "SSHFP",
+    "NAPTR",
+    "SPF",
+    "HINFO",
+    "LOC",
+    "DNAME",
+    "SVCB",
+    "HTTPS",
+];
+const CLASSES: &[&str] = &["IN", "CH", "HS"];
+
+#[derive(Parser)]
+pub struct ExportDnsArgs {
+    #[arg(long, value_enum, default_value_t = ExportFormat::Bind, help = "Format of the export")]
+    format: ExportFormat,
+    #[arg(long, help = "File to write the export to. Defaults to stdout")]
+    out: Option<PathBuf>,
+}
+
+#[derive(Parser)]
+pub struct ImportDnsArgs {
+    #[arg(help = "RFC 1035 zone file to import records from")]
+    file: PathBuf,
+    #[arg(
+        long,
+        action,
+        help = "Prints the records that would be created without creating them"
+    )]
+    dry_run: bool,
+}
+
+#[derive(ValueEnum, Clone, Debug)]
+pub enum ExportFormat {
+    Bind,
+}
+
+pub async fn export_dns(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &ExportDnsArgs,
+) -> ExitCode {
+    info!(
+        "Exporting dns for domain {} as {:?}",
+        domain_id, args.format
+    );
+
+    let domain = match client.get_domain(domain_id).await {
+        Ok(domain) => domain,
+        Err(err) => return log_and_fail_with_error("Failed to get domain", err),
+    };
+    let records = match client
+        .list_dns_records_with_filter(domain_id, None, None)
+        .await
+    {
+        Ok(records) => records,
+        Err(err) => return log_and_fail_with_error("Failed to list dns", err),
+    };
+
+    let zone = match args.format {
+        ExportFormat::Bind => to_zone_file(&domain.domain, &records),
+    };
+
+    match &args.out {
+        None => {
+            print!("{}", zone);
+            ExitCode::SUCCESS
+        }
+        Some(out) => match fs::write(out, zone) {
+            Ok(_) => {
+                println!("Exported {} records to {}", records.len(), out.display());
+                ExitCode::SUCCESS
+            }
+            Err(err) => log_and_fail_with_error("Failed to write export", err),
+        },
+    }
+}
+
+pub async fn import_dns(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &ImportDnsArgs,
+) -> ExitCode {
+    info!(
+        "Importing dns for domain {} from {}",
+        domain_id,
+        args.file.display()
+    );
+
+    let content = match fs::read_to_string(&args.file) {
+        Ok(content) => content,
+        Err(err) => return log_and_fail_with_error("Could not read zone file", err),
+    };
+    let domain = match client.get_domain(domain_id).await {
+        Ok(domain) => domain,
+        Err(err) => return log_and_fail_with_error("Failed to get domain", err),
+    };
+    let records = match parse_zone_file(&content, &domain.domain) {
+        Ok(records) => records,
+        Err(err) => return log_and_fail(format!("Could not parse zone file: {}", err)),
+    };
+    let existing = match client
+        .list_dns_records_with_filter(domain_id, None, None)
+        .await
+    {
+        Ok(records) => records,
+        Err(err) => return log_and_fail_with_error("Failed to list dns", err),
+    };
+
+    let mut failures = 0;
+    for record in records {
+        if existing
+            .iter()
+            .any(|existing| same_record(&existing.data, &record))
+        {
+            println!("= {}", describe(&record));
+            continue;
+        }
+        if let Err(err) = validate_type_specific_fields(&record) {
+            failures += 1;
+            eprintln!("Skipping {}: {}", describe(&record), err);
+            continue;
+        }
+
+        println!("+ {}", describe(&record));
+        if args.dry_run {
+            continue;
+        }
+        match client.add_dns_record(domain_id, record).await {
+            Ok(id) => info!("Created dns record with id {}", id),
+            Err(err) => {
+                failures += 1;
+                error!("Failed to create record: {:?}", err);
+                eprintln!("Failed to create record: {}", err);
+            }
+        }
+    }
+
+    if failures > 0 {
+        log_and_fail(format!("{} records could not be imported", failures))
+    } else {
+        ExitCode::SUCCESS
+    }
+}
+
+fn to_zone_file(domain: &str, records: &[ExistingDnsRecord]) -> String {
+    let mut zone = format!("$ORIGIN {}.\n$TTL {}\n", domain, DEFAULT_DNS_TTL);
+
+    for record in records {
+        let record = &record.data;
+        let rdata = match record.r#type {
+            DnsType::CNAME => absolute_name(&record.data),
+            DnsType::MX => format!(
+                "{} {}",
+                record.priority.unwrap_or_default(),
+                absolute_name(&record.data)
+            ),
+            DnsType::SRV => format!(
+                "{} {} {} {}",
+                record.priority.unwrap_or_default(),
+                record.weight.unwrap_or_default(),
+                record.port.unwrap_or_default(),
+                absolute_name(&record.data)
+            ),
+            DnsType::TXT => quote_txt(&record.data),
+            _ => record.data.clone(),
+        };
+        zone.push_str(&format!(
+            "{}\t{}\tIN\t{:?}\t{}\n",
+            record.host, record.ttl, record.r#type, rdata
+        ));
+    }
+
+    zone
+}
+
+fn absolute_name(name: &str) -> String {
+    if name.ends_with('.') {
+        name.to_string()
+    } else {
+        format!("{}.", name)
+    }
+}
+
+/// TXT strings are limited to 255 bytes, so longer values are split into several quoted strings
+/// which resolvers concatenate again. Splits never fall inside a multi-byte character.
+fn quote_txt(data: &str) -> String {
+    let mut chunks = Vec::new();
+    let mut chunk = String::new();
+    let mut length = 0;
+    for c in data.chars() {
+        if length + c.len_utf8() > 255 {
+            chunks.push(format!("\"{}\"", chunk));
+            chunk.clear();
+            length = 0;
+        }
+        length += c.len_utf8();
+        if matches!(c, '"' | '\\') {
+            chunk.push('\\');
+        }
+        chunk.push(c);
+    }
+    chunks.push(format!("\"{}\"", chunk));
+    chunks.join(" ")
+}
+
+/// Parses the subset of RFC 1035 needed for the record types supported by the API. `$ORIGIN` and
+/// `$TTL` are honored, and records of other known types, such as SOA and NS, are skipped.
+fn parse_zone_file(content: &str, domain: &str) -> Result<Vec<DnsRecord>, String> {
+    let apex = absolute_name(domain);
+    let mut origin = apex.clone();
+    let mut default_ttl = DEFAULT_DNS_TTL;
+    let mut last_owner: Option<String> = None;
+    let mut records = Vec::new();
+
+    for (line_number, line) in logical_lines(content)? {
+        let starts_with_owner = !line.starts_with(|c: char| c.is_whitespace());
+        let tokens = tokenize(&line)?;
+        let Some(first) = tokens.first() else {
+            continue;
+        };
+
+        if first.eq_ignore_ascii_case("$ORIGIN") {
+            let value = tokens
+                .get(1)
+                .ok_or(format!("line {}: $ORIGIN without value", line_number))?;
+            origin = qualify(value, &origin);
+            continue;
+        }
+        if first.eq_ignore_ascii_case("$TTL") {
+            default_ttl = tokens
+                .get(1)
+                .and_then(|ttl| parse_ttl(ttl))
+                .ok_or(format!("line {}: invalid $TTL", line_number))?;
+            continue;
+        }
+        if first.starts_with('$') {
+            warn!("Skipping unsupported directive on line {}", line_number);
+            continue;
+        }
+
+        let mut tokens = tokens.into_iter();
+        let owner = if starts_with_owner {
+            let owner = qualify(&tokens.next().unwrap_or_default(), &origin);
+            last_owner = Some(owner.clone());
+            owner
+        } else {
+            last_owner
+                .clone()
+                .ok_or(format!("line {}: record without owner", line_number))?
+        };
+
+        let mut ttl = default_ttl;
+        let mut record_type = None;
+        for token in tokens.by_ref() {
+            if let Some(value) = parse_ttl(&token) {
+                ttl = value;
+            } else if CLASSES
+                .iter()
+                .any(|class| token.eq_ignore_ascii_case(class))
+            {
+                continue;
+            } else {
+                record_type = Some(token);
+                break;
+            }
+        }
+        let record_type =
+            record_type.ok_or(format!("line {}: record without type", line_number))?;
+        let rdata: Vec<String> = tokens.collect();
+
+        let Ok(dns_type) = DnsTypeArg::from_str(&record_type, true) else {
+            if UNSUPPORTED_TYPES
+                .iter()
+                .any(|unsupported| record_type.eq_ignore_ascii_case(unsupported))
+            {
+                warn!(
+                    "Skipping unsupported {} record on line {}",
+                    record_type, line_number
+                );
+                continue;
+            }
+            return Err(format!(
+                "line {}: '{}' is not a known class or record type",
+                line_number, record_type
+            ));
+        };
+
+        records.push(
+            relative_host(&owner, &apex)
+                .and_then(|host| to_dns_record(host, ttl, dns_type, rdata, &origin))
+                .map_err(|err| format!("line {}: {}", line_number, err))?,
+        );
+    }
+
+    Ok(records)
+}
+
+/// TTLs are given in seconds, or with BIND units like `1h` or `1h30m`: s, m, h, d and w.
+fn parse_ttl(token: &str) -> Option<i32> {
+    if !token.starts_with(|c: char| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut total: i32 = 0;
+    let mut value: i32 = 0;
+    let mut has_value = false;
+    for c in token.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            value = value.checked_mul(10)?.checked_add(digit as i32)?;
+            has_value = true;
+            continue;
+        }
+        let seconds = match c.to_ascii_lowercase() {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            'w' => 7 * 24 * 60 * 60,
+            _ => return None,
+        };
+        if !has_value {
+            return None;
+        }
+        total = total.checked_add(value.checked_mul(seconds)?)?;
+        value = 0;
+        has_value = false;
+    }
+    total.checked_add(value)
+}
+
+fn to_dns_record(
+    host: String,
+    ttl: i32,
+    dns_type: DnsTypeArg,
+    rdata: Vec<String>,
+    origin: &str,
+) -> Result<DnsRecord, String> {
+    let number = |index: usize| -> Result<Option<i32>, String> {
+        rdata
+            .get(index)
+            .ok_or(format!("missing field {} for {:?}", index + 1, dns_type))?
+            .parse()
+            .map(Some)
+            .map_err(|err| format!("invalid number: {}", err))
+    };
+    let target = |index: usize| -> Result<String, String> {
+        rdata
+            .get(index)
+            .map(|name| qualify(name, origin).trim_end_matches('.').to_string())
+            .ok_or(format!("missing target for {:?}", dns_type))
+    };
+
+    let (data, priority, weight, port) = match dns_type {
+        DnsTypeArg::A | DnsTypeArg::AAAA => (
+            rdata.first().cloned().ok_or("missing address")?,
+            None,
+            None,
+            None,
+        ),
+        DnsTypeArg::CNAME => (target(0)?, None, None, None),
+        DnsTypeArg::MX => (target(1)?, number(0)?, None, None),
+        DnsTypeArg::SRV => (target(3)?, number(0)?, number(1)?, number(2)?),
+        DnsTypeArg::TXT => (rdata.concat(), None, None, None),
+    };
+
+    Ok(DnsRecord {
+        host,
+        ttl,
+        r#type: map_dns_type(dns_type),
+        data,
+        priority,
+        weight,
+        port,
+    })
+}
+
+/// Strips comments and joins records spanning several lines with parentheses, keeping the line
+/// number where each record starts.
+fn logical_lines(content: &str) -> Result<Vec<(usize, String)>, String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    let mut start = 0;
+    let mut depth = 0;
+
+    for (index, line) in content.lines().enumerate() {
+        let mut in_quotes = false;
+        let mut escaped = false;
+        let mut stripped = String::new();
+        for c in line.chars() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_quotes => escaped = true,
+                '"' => in_quotes = !in_quotes,
+                ';' if !in_quotes => break,
+                '(' if !in_quotes => {
+                    depth += 1;
+                    stripped.push(' ');
+                    continue;
+                }
+                ')' if !in_quotes => {
+                    depth -= 1;
+                    stripped.push(' ');
+                    continue;
+                }
+                _ => (),
+            }
+            stripped.push(c);
+        }
+
+        if current.is_empty() {
+            start = index + 1;
+            current = stripped;
+        } else {
+            current.push(' ');
+            current.push_str(&stripped);
+        }
+
+        if depth < 0 {
+            return Err(format!("line {}: unbalanced parentheses", index + 1));
+        }
+        if depth == 0 {
+            if !current.trim().is_empty() {
+                lines.push((start, current.trim_end().to_string()));
+            }
+            current = String::new();
+        }
+    }
+
+    if depth != 0 {
+        return Err(format!("line {}: unbalanced parentheses", start));
+    }
+    Ok(lines)
+}
+
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        let mut token = String::new();
+        if c == '"' {
+            loop {
+                match chars.next() {
+                    None => return Err("unterminated quoted string".to_string()),
+                    Some('"') => break,
+                    Some('\\') => token.extend(chars.next()),
+                    Some(c) => token.push(c),
+                }
+            }
+        } else {
+            token.push(c);
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                token.push(c);
+            }
+        }
+        tokens.push(token);
+    }
+
+    Ok(tokens)
+}
+
+fn qualify(name: &str, origin: &str) -> String {
+    if name == "@" {
+        origin.to_string()
+    } else if name.ends_with('.') {
+        name.to_string()
+    } else {
+        format!("{}.{}", name, origin)
+    }
+}
+
+/// Hosts in the API are relative to the domain, whatever `$ORIGIN` the owner was written under.
+/// Owners outside the domain are rejected, as the API would create them below the domain.
+fn relative_host(owner: &str, apex: &str) -> Result<String, String> {
+    if owner.eq_ignore_ascii_case(apex) {
+        return Ok("@".to_string());
+    }
+    let suffix = format!(".{}", apex);
+    let end = owner.len().checked_sub(suffix.len());
+    match end.and_then(|end| Some((end, owner.get(end..)?))) {
+        Some((end, tail)) if end > 0 && tail.eq_ignore_ascii_case(&suffix) => {
+            Ok(owner[..end].to_string())
+        }
+        _ => Err(format!(
+            "{} is outside {}",
+            owner.trim_end_matches('.'),
+            apex.trim_end_matches('.')
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use domeneshop_client::endpoints::dns::DnsId;
+
+    use super::*;
+
+    fn describe_all(records: &[DnsRecord]) -> Vec<String> {
+        records.iter().map(describe).collect()
+    }
+
+    #[test]
+    fn parses_ttl_units() {
+        assert_eq!(parse_ttl("300"), Some(300));
+        assert_eq!(parse_ttl("1h"), Some(3600));
+        assert_eq!(parse_ttl("1h30m"), Some(5400));
+        assert_eq!(parse_ttl("2D"), Some(172800));
+        assert_eq!(parse_ttl("1w"), Some(604800));
+        assert_eq!(parse_ttl("A"), None);
+        assert_eq!(parse_ttl("1x"), None);
+        assert_eq!(parse_ttl("99999999999"), None);
+    }
+
+    #[test]
+    fn reads_ttls_with_units() {
+        let zone = "$TTL 1d\nwww 1h IN A 192.0.2.1\nmail IN A 192.0.2.2\n";
+        let records = parse_zone_file(zone, "example.com").unwrap();
+
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].ttl, 3600);
+        assert_eq!(records[1].ttl, 86400);
+    }
+
+    #[test]
+    fn fails_on_unknown_type() {
+        let result = parse_zone_file("www 1q IN A 192.0.2.1\n", "example.com");
+        assert!(result.unwrap_err().contains("line 1"));
+    }
+
+    #[test]
+    fn skips_types_the_api_does_not_support() {
+        let zone = "@ IN NS ns1.example.com.\n@ IN A 192.0.2.1\n";
+        let records = parse_zone_file(zone, "example.com").unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].data, "192.0.2.1");
+    }
+
+    #[test]
+    fn joins_records_in_parentheses() {
+        let zone = "_sip._tcp 600 IN SRV ( 10 ; priority\n  20 5060\n  sip.example.com. )\n";
+        let records = parse_zone_file(zone, "example.com").unwrap();
+
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].host, "_sip._tcp");
+        assert_eq!(records[0].priority, Some(10));
+        assert_eq!(records[0].weight, Some(20));
+        assert_eq!(records[0].port, Some(5060));
+        assert_eq!(records[0].data, "sip.example.com");
+    }
+
+    #[test]
+    fn reads_quoted_txt_strings() {
+        let zone = "@ IN TXT \"v=spf1 ; -all\" \"with \\\"quotes\\\"\"\n";
+        let records = parse_zone_file(zone, "example.com").unwrap();
+
+        assert_eq!(records[0].data, "v=spf1 ; -allwith \"quotes\"");
+    }
+
+    #[test]
+    fn qualifies_names_with_origin() {
+        let zone = "$ORIGIN sub.example.com.\nwww IN CNAME host\nmail IN MX 10 mx.example.net.\n";
+        let records = parse_zone_file(zone, "example.com").unwrap();
+
+        assert_eq!(records[0].host, "www.sub");
+        assert_eq!(records[0].data, "host.sub.example.com");
+        assert_eq!(records[1].host, "mail.sub");
+        assert_eq!(records[1].data, "mx.example.net");
+    }
+
+    #[test]
+    fn inherits_owner_from_previous_record() {
+        let zone = "www IN A 192.0.2.1\n    IN AAAA 2001:db8::1\n";
+        let records = parse_zone_file(zone, "example.com").unwrap();
+
+        assert_eq!(records[1].host, "www");
+        assert_eq!(records[1].data, "2001:db8::1");
+        assert!(parse_zone_file("  IN A 192.0.2.1\n", "example.com").is_err());
+    }
+
+    #[test]
+    fn rejects_owners_outside_the_domain() {
+        let zone = "www IN A 192.0.2.1\nwww.example.net. IN A 192.0.2.2\n";
+        let err = parse_zone_file(zone, "example.com").unwrap_err();
+
+        assert!(err.starts_with("line 2:"), "{}", err);
+        assert!(parse_zone_file("www.notexample.com. IN A 192.0.2.1\n", "example.com").is_err());
+    }
+
+    #[test]
+    fn splits_txt_strings_by_bytes() {
+        let quoted = quote_txt(&"ø".repeat(200));
+        let strings: Vec<&str> = quoted.split(' ').collect();
+
+        assert_eq!(strings.len(), 2);
+        assert_eq!(strings[0].trim_matches('"').len(), 254);
+        assert_eq!(strings[1].trim_matches('"').len(), 146);
+    }
+
+    #[test]
+    fn imports_what_it_exports() {
+        let record = |host: &str, r#type, data: &str, priority, weight, port| DnsRecord {
+            host: host.to_string(),
+            ttl: 600,
+            r#type,
+            data: data.to_string(),
+            priority,
+            weight,
+            port,
+        };
+        let records = vec![
+            record("@", DnsType::A, "192.0.2.1", None, None, None),
+            record("www", DnsType::AAAA, "2001:db8::1", None, None, None),
+            record("blog", DnsType::CNAME, "example.net", None, None, None),
+            record("@", DnsType::MX, "mail.example.com", Some(10), None, None),
+            record(
+                "_sip._tcp",
+                DnsType::SRV,
+                "sip.example.com",
+                Some(1),
+                Some(2),
+                Some(5060),
+            ),
+            record(
+                "@",
+                DnsType::TXT,
+                &format!("\"quoted\" {}", "x".repeat(300)),
+                None,
+                None,
+                None,
+            ),
+        ];
+        let existing: Vec<ExistingDnsRecord> = records
+            .iter()
+            .enumerate()
+            .map(|(id, data)| ExistingDnsRecord {
+                id: id as DnsId,
+                data: record(
+                    &data.host,
+                    data.r#type.clone(),
+                    &data.data,
+                    data.priority,
+                    data.weight,
+                    data.port,
+                ),
+            })
+            .collect();
+
+        let zone = to_zone_file("example.com", &existing);
+        let imported = parse_zone_file(&zone, "example.com").unwrap();
+
+        assert_eq!(describe_all(&imported), describe_all(&records));
+    }
+}
@@ -1,12 +1,20 @@
-use std::process::ExitCode;
+use std::{path::PathBuf, process::ExitCode, time::Duration};
 
 use clap::*;
+use clap_complete::ArgValueCandidates;
 use domeneshop_client::{
     client::DomeneshopClient,
     endpoints::domains::{Domain, DomainId, WebhotelType},
 };
 
-use crate::log_and_fail_with_error;
+use serde_json::{json, Value};
+
+use crate::{
+    completion::{cache_domains, domain_id_candidates},
+    duration::parse_duration,
+    log_and_fail, log_and_fail_with_error,
+    output::{Output, Render},
+};
 
 #[derive(Parser)]
 pub struct DomainArgs {
@@ -18,6 +26,8 @@ pub struct DomainArgs {
 pub enum Command {
     List(ListDomainArgs),
     Get(GetDomainArgs),
+    /// Lists domains that expire soon, and fails if there are any
+    Expiring(ExpiringDomainArgs),
 }
 
 #[derive(Parser)]
@@ -26,31 +36,51 @@ pub struct ListDomainArgs {
     filter: Option<String>,
 }
 
+#[derive(Parser)]
+pub struct ExpiringDomainArgs {
+    #[arg(
+        long,
+        value_parser = parse_duration,
+        default_value = "30d",
+        help = "How soon a domain must expire to be listed, e.g. 30d"
+    )]
+    within: Duration,
+}
+
 #[derive(Parser)]
 pub struct GetDomainArgs {
+    #[arg(add = ArgValueCandidates::new(domain_id_candidates))]
     id: DomainId,
 }
 
-pub async fn handle_domains(args: &DomainArgs, client: &DomeneshopClient) -> ExitCode {
+pub async fn handle_domains(
+    args: &DomainArgs,
+    client: &DomeneshopClient,
+    cache_dir: &PathBuf,
+    output: &Output,
+) -> ExitCode {
     match &args.command {
-        Command::List(args) => list_domains(client, args).await,
-        Command::Get(args) => get_domain(client, args.id).await,
+        Command::List(args) => list_domains(client, args, cache_dir, output).await,
+        Command::Get(args) => get_domain(client, args.id, output).await,
+        Command::Expiring(args) => expiring_domains(client, args, output).await,
     }
 }
 
-async fn get_domain(client: &DomeneshopClient, id: DomainId) -> ExitCode {
+async fn get_domain(client: &DomeneshopClient, id: DomainId, output: &Output) -> ExitCode {
     info!("Getting domain with id {}", id);
 
     match client.get_domain(id).await {
-        Ok(domain) => {
-            print_domain(domain);
-            ExitCode::SUCCESS
-        }
+        Ok(domain) => output.item(&domain),
         Err(err) => log_and_fail_with_error("Failed to get domain", err),
     }
 }
 
-async fn list_domains(client: &DomeneshopClient, args: &ListDomainArgs) -> ExitCode {
+async fn list_domains(
+    client: &DomeneshopClient,
+    args: &ListDomainArgs,
+    cache_dir: &PathBuf,
+    output: &Output,
+) -> ExitCode {
     info!("Listing domains with filter {:?} ...", args.filter);
 
     let response = match &args.filter {
@@ -61,43 +91,128 @@ async fn list_domains(client: &DomeneshopClient, args: &ListDomainArgs) -> ExitC
     match response {
         Err(err) => log_and_fail_with_error("Failed to list domains", err),
         Ok(domains) => {
-            println!("Got {} domains.", domains.len());
-            for domain in domains {
-                println!("{}: {}", domain.id, domain.domain);
+            info!("Got {} domains", domains.len());
+            if args.filter.is_none() {
+                cache_domains(cache_dir, &domains);
             }
-            ExitCode::SUCCESS
+            output.list(&domains)
         }
     }
 }
 
-fn print_domain(domain: Domain) {
-    println!("{}", domain.domain);
-    println!("Registrant: {}", domain.registrant);
-    if let Some(registered_date) = domain.registered_date {
-        println!("Registered at {}", registered_date);
+/// A domain that expires within the window, with the reasons it needs attention.
+struct ExpiringDomain<'a> {
+    domain: &'a Domain,
+    days_left: i64,
+    warnings: Vec<&'static str>,
+}
+
+impl Render for ExpiringDomain<'_> {
+    const DEFAULT_COLUMNS: &'static [&'static str] = &[
+        "id",
+        "domain",
+        "expiry_date",
+        "days_left",
+        "renew",
+        "warnings",
+    ];
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        let mut fields = self.domain.fields();
+        fields.push(("days_left", json!(self.days_left)));
+        fields.push(("warnings", json!(self.warnings)));
+        fields
     }
-    println!("Expires at {}", domain.expiry_date);
-    println!("Status: {}", domain.status);
-    println!("Renewal: {}", domain.renew);
-    println!("Nameservers");
-    for ns in domain.nameservers {
-        println!("\t{}", ns);
+}
+
+async fn expiring_domains(
+    client: &DomeneshopClient,
+    args: &ExpiringDomainArgs,
+    output: &Output,
+) -> ExitCode {
+    let within_days = (args.within.as_secs() / (24 * 60 * 60)) as i64;
+    info!("Listing domains expiring within {} days ...", within_days);
+
+    let domains = match client.list_domains().await {
+        Ok(domains) => domains,
+        Err(err) => return log_and_fail_with_error("Failed to list domains", err),
+    };
+
+    let today = chrono::Utc::now().date_naive();
+    let mut expiring: Vec<ExpiringDomain> = domains
+        .iter()
+        .map(|domain| ExpiringDomain {
+            domain,
+            days_left: (domain.expiry_date - today).num_days(),
+            warnings: expiry_warnings(domain),
+        })
+        .filter(|expiring| expiring.days_left <= within_days)
+        .collect();
+    expiring.sort_by_key(|expiring| expiring.days_left);
+
+    let shown = output.list(&expiring);
+    if expiring.is_empty() || shown != ExitCode::SUCCESS {
+        shown
+    } else {
+        log_and_fail(format!(
+            "{} of {} domains expire within {} days",
+            expiring.len(),
+            domains.len(),
+            within_days
+        ))
+    }
+}
+
+fn expiry_warnings(domain: &Domain) -> Vec<&'static str> {
+    let mut warnings = Vec::new();
+    if !domain.renew {
+        warnings.push("renewal disabled");
+    }
+    if !domain.status.to_string().eq_ignore_ascii_case("active") {
+        warnings.push("not active");
     }
-    println!("Services:");
+    warnings
+}
+
+impl Render for Domain {
+    const DEFAULT_COLUMNS: &'static [&'static str] =
+        &["id", "domain", "expiry_date", "status", "renew"];
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("id", json!(self.id)),
+            ("domain", json!(self.domain)),
+            ("registrant", json!(self.registrant)),
+            (
+                "registered_date",
+                json!(self.registered_date.as_ref().map(ToString::to_string)),
+            ),
+            ("expiry_date", json!(self.expiry_date.to_string())),
+            ("status", json!(self.status.to_string())),
+            ("renew", json!(self.renew)),
+            ("nameservers", json!(self.nameservers)),
+            ("services", json!(service_names(self))),
+        ]
+    }
+}
+
+fn service_names(domain: &Domain) -> Vec<&'static str> {
+    let mut services = Vec::new();
     if domain.services.registrar {
-        println!("\tRegistrar");
+        services.push("Registrar");
     }
     if domain.services.dns {
-        println!("\tDNS");
+        services.push("DNS");
     }
     if domain.services.email {
-        println!("\tE-Mail");
+        services.push("E-Mail");
     }
     match domain.services.webhotel {
-        WebhotelType::WebXLarge => println!("Webhotel Extra Large"),
-        WebhotelType::WebLarge => println!("Webhotel Large"),
-        WebhotelType::WebMedium => println!("Webhotel Medium"),
-        WebhotelType::WebSmall => println!("Webhotel Starter"),
+        WebhotelType::WebXLarge => services.push("Webhotel Extra Large"),
+        WebhotelType::WebLarge => services.push("Webhotel Large"),
+        WebhotelType::WebMedium => services.push("Webhotel Medium"),
+        WebhotelType::WebSmall => services.push("Webhotel Starter"),
         _ => (),
     };
+    services
 }
@@ -1,42 +1,284 @@
-use std::io::Write;
-use std::{fs, net::IpAddr, path::PathBuf, process::ExitCode};
+mod history;
+mod hooks;
+mod interface;
+mod resolvers;
+mod verify;
+mod watch;
 
-use clap::Parser;
+use std::{
+    collections::BTreeMap, fs, net::IpAddr, path::PathBuf, process::ExitCode, time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use clap::{ArgGroup, Parser, Subcommand};
 use domeneshop_client::client::DomeneshopClient;
 
-use crate::constants::{DYNDNS_EXECUTION_LOG_FILENAME, LAST_IP_FILENAME};
-use crate::log_and_fail_with_error;
+use history::{HistoryArgs, HistoryEntry, Outcome};
+use resolvers::IpSource;
+
+use crate::constants::{LAST_IPV4_FILENAME, LAST_IPV6_FILENAME};
+use crate::duration::parse_duration;
+use crate::output::Output;
+use crate::{log_and_fail, log_and_fail_with_error};
 
 #[derive(Parser)]
+#[command(
+    group(ArgGroup::new("family").args(["ipv4", "ipv6", "both"])),
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 pub struct Command {
-    domain: String,
+    #[command(subcommand)]
+    subcommand: Option<DyndnsCommand>,
+    #[arg(
+        help = "Hostnames to update, e.g. home.example.com. Defaults to dyndns.hosts in config.toml"
+    )]
+    hosts: Vec<String>,
+    #[arg(long, help = "File with hostnames to update, one per line")]
+    hosts_file: Option<PathBuf>,
+    #[arg(
+        long,
+        action,
+        help = "Updates the A record with the public IPv4 address"
+    )]
+    ipv4: bool,
+    #[arg(
+        long,
+        action,
+        help = "Updates the AAAA record with the public IPv6 address"
+    )]
+    ipv6: bool,
+    #[arg(long, action, help = "Updates both the A and the AAAA record")]
+    both: bool,
+    #[arg(
+        long,
+        action,
+        help = "Keeps running and updates the record whenever the ip changes"
+    )]
+    watch: bool,
+    #[arg(
+        long,
+        value_parser = parse_interval,
+        default_value = "5m",
+        requires = "watch",
+        help = "Time between checks in watch mode, e.g. 30s, 5m or 1h"
+    )]
+    interval: Duration,
+    #[arg(
+        long,
+        value_delimiter = ',',
+        default_value = "public",
+        help = "Comma separated sources of the public ip, tried in order: public, http(s)://<url>, opendns, interface:<name>, upnp, natpmp or fixed:<ip>"
+    )]
+    ip_source: Vec<IpSource>,
+    #[arg(
+        long,
+        conflicts_with = "ip_source",
+        help = "Reads the ip from a local network interface instead of looking it up"
+    )]
+    interface: Option<String>,
+    #[arg(
+        long,
+        default_value_t = 1,
+        help = "Number of ip sources that must answer. All answers must agree"
+    )]
+    ip_quorum: usize,
+    #[arg(
+        long,
+        action,
+        help = "Checks the actual record instead of trusting the cached ip, and updates it if it has drifted"
+    )]
+    verify: bool,
+    #[arg(
+        long = "on-change",
+        value_name = "COMMAND",
+        help = "Shell command to run when the ip of a host changes. HOST, OLD_IP and NEW_IP are set in its environment"
+    )]
+    on_change: Vec<String>,
+    #[arg(
+        long = "webhook",
+        value_name = "URL",
+        help = "Url to POST a json body with host, old_ip and new_ip to when the ip of a host changes"
+    )]
+    webhooks: Vec<String>,
+}
+
+/// An interval of zero would check the ip in a busy loop.
+fn parse_interval(input: &str) -> Result<Duration, String> {
+    match parse_duration(input)? {
+        interval if interval.is_zero() => Err("The interval must be longer than zero".to_string()),
+        interval => Ok(interval),
+    }
+}
+
+#[derive(Subcommand)]
+enum DyndnsCommand {
+    /// Shows previous updates and failures
+    History(HistoryArgs),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum IpFamily {
+    V4,
+    V6,
+}
+
+impl Command {
+    /// Families to update. Without any flag, whichever address the lookup returns is used.
+    fn families(&self) -> Vec<Option<IpFamily>> {
+        if self.both {
+            vec![Some(IpFamily::V4), Some(IpFamily::V6)]
+        } else if self.ipv4 {
+            vec![Some(IpFamily::V4)]
+        } else if self.ipv6 {
+            vec![Some(IpFamily::V6)]
+        } else {
+            vec![None]
+        }
+    }
+
+    fn ip_sources(&self) -> Vec<IpSource> {
+        match &self.interface {
+            Some(name) => vec![IpSource::Interface(name.clone())],
+            None => self.ip_source.clone(),
+        }
+    }
+
+    /// Hostnames given as arguments followed by those in `--hosts-file`. Empty lines and lines
+    /// starting with `#` in the file are ignored. Without either, the configured hosts are used.
+    fn hosts(&self, default_hosts: &[String]) -> Result<Vec<String>, String> {
+        if self.hosts.is_empty() && self.hosts_file.is_none() {
+            return Ok(default_hosts.to_vec());
+        }
+        let mut hosts = self.hosts.clone();
+        if let Some(file) = &self.hosts_file {
+            let content = fs::read_to_string(file)
+                .map_err(|err| format!("Could not read {}: {}", file.display(), err))?;
+            hosts.extend(
+                content
+                    .lines()
+                    .map(str::trim)
+                    .filter(|line| !line.is_empty() && !line.starts_with('#'))
+                    .map(String::from),
+            );
+        }
+        let mut unique: Vec<String> = Vec::new();
+        for host in hosts {
+            if !unique.contains(&host) {
+                unique.push(host);
+            }
+        }
+        Ok(unique)
+    }
+}
+
+/// `dyndns history` only reads the local history, so it is handled before credentials are
+/// needed. Returns `None` for the other dyndns commands.
+pub fn handle_dyndns_history(
+    command: &Command,
+    data_dir: &PathBuf,
+    output: &Output,
+) -> Option<ExitCode> {
+    match &command.subcommand {
+        Some(DyndnsCommand::History(args)) => Some(history::show_history(args, data_dir, output)),
+        None => None,
+    }
 }
 
 pub async fn handle_dyndns(
     command: &Command,
     client: &DomeneshopClient,
+    default_hosts: &[String],
+    data_dir: &PathBuf,
+) -> ExitCode {
+    let hosts = match command.hosts(default_hosts) {
+        Ok(hosts) if hosts.is_empty() => return log_and_fail(
+            "No hostnames to update. Give them as arguments, with --hosts-file or in config.toml",
+        ),
+        Ok(hosts) => hosts,
+        Err(err) => return log_and_fail(err),
+    };
+
+    if command.watch {
+        watch::watch(command, &hosts, client, data_dir).await
+    } else {
+        update_all(command, &hosts, client, data_dir).await
+    }
+}
+
+async fn update_all(
+    command: &Command,
+    hosts: &[String],
+    client: &DomeneshopClient,
     data_dir: &PathBuf,
 ) -> ExitCode {
-    info!("Updating dyndns ...");
-    match public_ip::addr().await {
-        None => {
-            error!("Unable to resolve ip");
-            ExitCode::FAILURE
+    info!("Updating dyndns for {} ...", hosts.join(", "));
+
+    let mut result = ExitCode::SUCCESS;
+    for family in command.families() {
+        let (ip, resolver) =
+            match resolvers::resolve(&command.ip_sources(), family, command.ip_quorum).await {
+                Ok(resolved) => resolved,
+                Err(err) => {
+                    error!("Unable to resolve ip: {}", err);
+                    eprintln!("Unable to resolve ip: {}", err);
+                    for host in hosts {
+                        history::record(
+                            data_dir,
+                            HistoryEntry {
+                                timestamp: chrono::Utc::now(),
+                                host: host.clone(),
+                                old_ip: None,
+                                new_ip: None,
+                                resolver: None,
+                                outcome: Outcome::Failed,
+                                error: Some(format!("Unable to resolve ip: {}", err)),
+                            },
+                        );
+                    }
+                    result = ExitCode::FAILURE;
+                    continue;
+                }
+            };
+        for host in hosts {
+            let updated = update_dyndns(client, command, host, ip, &resolver, data_dir).await;
+            if updated != ExitCode::SUCCESS {
+                result = ExitCode::FAILURE;
+            }
         }
-        Some(ip) => update_dyndns(client, &command.domain, ip, data_dir).await,
     }
+    result
 }
+
 async fn update_dyndns(
     client: &DomeneshopClient,
+    command: &Command,
     domain: &String,
     ip: IpAddr,
+    resolver: &str,
     data_dir: &PathBuf,
 ) -> ExitCode {
-    let last_ip_file = get_last_ip_file(data_dir);
+    let last_ip_file = get_last_ip_file(data_dir, domain, family_of(ip));
     let last_ip = get_last_ip_address(&last_ip_file);
-    if let Some(last_ip) = last_ip {
-        if last_ip == ip {
-            let message = "IP hasn't changed since last time. Exiting";
+    if last_ip == Some(ip) {
+        let in_sync = if command.verify {
+            match verify::record_matches(client, domain, ip).await {
+                Some(true) => true,
+                Some(false) => {
+                    warn!("{domain}: Record has drifted from cached ip {ip}, updating");
+                    false
+                }
+                None => {
+                    warn!("{domain}: Could not verify record, updating");
+                    false
+                }
+            }
+        } else {
+            true
+        };
+
+        if in_sync {
+            let message = format!("{}: {} hasn't changed since last time", domain, ip);
             info!("{message}");
             println!("{message}");
             return ExitCode::SUCCESS;
@@ -44,15 +286,75 @@ async fn update_dyndns(
     }
 
     let result = client.update_dyndns(domain, Some(ip)).await;
+    let mut entry = HistoryEntry {
+        timestamp: chrono::Utc::now(),
+        host: domain.clone(),
+        old_ip: last_ip,
+        new_ip: Some(ip),
+        resolver: Some(resolver.to_string()),
+        outcome: Outcome::Updated,
+        error: None,
+    };
     match result {
         Ok(_) => {
-            info!("Updated ip to {ip}");
-            println!("Updated ip to {ip}");
+            info!("{domain}: Updated ip to {ip}");
+            println!("{domain}: Updated ip to {ip}");
             update_last_ip(ip, &last_ip_file);
-            log_execution(ip, last_ip, &data_dir);
-            return ExitCode::SUCCESS;
+            history::record(data_dir, entry);
+            if last_ip != Some(ip) {
+                hooks::run_hooks(&command.on_change, &command.webhooks, domain, last_ip, ip).await;
+            }
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            entry.outcome = Outcome::Failed;
+            entry.error = Some(err.to_string());
+            history::record(data_dir, entry);
+            log_and_fail_with_error("Error while updating dns settings", err)
+        }
+    }
+}
+
+/// Time of the last successful update of each host, according to the history.
+pub fn last_updates(data_dir: &PathBuf) -> Result<BTreeMap<String, DateTime<Utc>>, String> {
+    let mut updates = BTreeMap::new();
+    for entry in history::read_history(data_dir)? {
+        if entry.outcome == Outcome::Updated {
+            updates.insert(entry.host, entry.timestamp);
         }
-        Err(err) => log_and_fail_with_error("Error while updating dns settings", err),
+    }
+    Ok(updates)
+}
+
+/// The ips last set for each host, read from the state files.
+pub fn current_ips(data_dir: &PathBuf) -> Vec<(String, IpAddr)> {
+    let entries = match fs::read_dir(data_dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            warn!("Could not read {}: {}", data_dir.display(), err);
+            return Vec::new();
+        }
+    };
+
+    let mut ips: Vec<(String, IpAddr)> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().to_string_lossy().to_string();
+            let host = [LAST_IPV4_FILENAME, LAST_IPV6_FILENAME]
+                .iter()
+                .find_map(|prefix| name.strip_prefix(&format!("{}.", prefix)))?
+                .to_string();
+            get_last_ip_address(&entry.path()).map(|ip| (host, ip))
+        })
+        .collect();
+    ips.sort();
+    ips
+}
+
+fn family_of(ip: IpAddr) -> IpFamily {
+    match ip {
+        IpAddr::V4(_) => IpFamily::V4,
+        IpAddr::V6(_) => IpFamily::V6,
     }
 }
 
@@ -79,32 +381,12 @@ fn update_last_ip(ip: IpAddr, last_ip_file: &PathBuf) {
     }
 }
 
-fn get_last_ip_file(data_dir: &PathBuf) -> PathBuf {
+fn get_last_ip_file(data_dir: &PathBuf, host: &str, family: IpFamily) -> PathBuf {
+    let filename = match family {
+        IpFamily::V4 => LAST_IPV4_FILENAME,
+        IpFamily::V6 => LAST_IPV6_FILENAME,
+    };
     let mut last_ip = data_dir.clone();
-    last_ip.push(LAST_IP_FILENAME);
+    last_ip.push(format!("{}.{}", filename, host));
     last_ip
 }
-
-fn log_execution(current_ip: IpAddr, last_ip: Option<IpAddr>, data_dir: &PathBuf) {
-    let mut log_file = data_dir.clone();
-    log_file.push(DYNDNS_EXECUTION_LOG_FILENAME);
-
-    let file_result = fs::OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_file);
-    match file_result {
-        Err(err) => warn!("Cannot log execution: {}", err),
-        Ok(mut file) => {
-            if let Err(e) = writeln!(
-                file,
-                "{}: Updated from ip {:?} to {}",
-                chrono::Utc::now(),
-                last_ip,
-                current_ip
-            ) {
-                error!("Couldn't write to file: {}", e);
-            }
-        }
-    }
-}
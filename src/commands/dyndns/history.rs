@@ -0,0 +1,157 @@
+use std::{
+    fs,
+    io::{BufRead, BufReader, Write},
+    net::IpAddr,
+    path::PathBuf,
+    process::ExitCode,
+};
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{
+    constants::DYNDNS_HISTORY_FILENAME,
+    duration::parse_duration,
+    log_and_fail,
+    output::{Output, Render},
+};
+
+#[derive(Parser)]
+pub struct HistoryArgs {
+    #[arg(
+        long,
+        value_parser = parse_since,
+        help = "Only shows entries after a date (2025-01-31), a timestamp (RFC 3339) or a duration ago (7d)"
+    )]
+    since: Option<DateTime<Utc>>,
+    #[arg(long, help = "Only shows entries for the given hostname")]
+    host: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Updated,
+    Failed,
+}
+
+/// One line of the dyndns history file, which holds a json object per line.
+#[derive(Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Utc>,
+    pub host: String,
+    pub old_ip: Option<IpAddr>,
+    pub new_ip: Option<IpAddr>,
+    pub resolver: Option<String>,
+    pub outcome: Outcome,
+    pub error: Option<String>,
+}
+
+impl Render for HistoryEntry {
+    const DEFAULT_COLUMNS: &'static [&'static str] =
+        &["timestamp", "host", "old_ip", "new_ip", "outcome", "error"];
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("timestamp", json!(self.timestamp.to_rfc3339())),
+            ("host", json!(self.host)),
+            ("old_ip", json!(self.old_ip)),
+            ("new_ip", json!(self.new_ip)),
+            ("resolver", json!(self.resolver)),
+            ("outcome", json!(self.outcome)),
+            ("error", json!(self.error)),
+        ]
+    }
+}
+
+pub fn show_history(args: &HistoryArgs, data_dir: &PathBuf, output: &Output) -> ExitCode {
+    info!(
+        "Showing dyndns history since {:?} for host {:?}",
+        args.since, args.host
+    );
+
+    match read_history(data_dir) {
+        Err(err) => log_and_fail(err),
+        Ok(entries) => {
+            let entries: Vec<HistoryEntry> = entries
+                .into_iter()
+                .filter(|entry| args.since.is_none_or(|since| entry.timestamp >= since))
+                .filter(|entry| {
+                    args.host
+                        .as_ref()
+                        .is_none_or(|host| entry.host.eq_ignore_ascii_case(host))
+                })
+                .collect();
+            output.list(&entries)
+        }
+    }
+}
+
+pub fn record(data_dir: &PathBuf, entry: HistoryEntry) {
+    let line = match serde_json::to_string(&entry) {
+        Ok(line) => line,
+        Err(err) => {
+            warn!("Cannot serialize history entry: {}", err);
+            return;
+        }
+    };
+
+    let file_result = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(history_file(data_dir));
+    match file_result {
+        Err(err) => warn!("Cannot record history: {}", err),
+        Ok(mut file) => {
+            if let Err(e) = writeln!(file, "{}", line) {
+                error!("Couldn't write to file: {}", e);
+            }
+        }
+    }
+}
+
+/// Reads all entries, oldest first. Lines that can't be parsed are skipped with a warning, so a
+/// single corrupt line doesn't hide the rest of the history.
+pub fn read_history(data_dir: &PathBuf) -> Result<Vec<HistoryEntry>, String> {
+    let file = history_file(data_dir);
+    let reader = match fs::File::open(&file) {
+        Ok(reader) => BufReader::new(reader),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(format!("Could not read {}: {}", file.display(), err)),
+    };
+
+    let mut entries = Vec::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|err| format!("Could not read {}: {}", file.display(), err))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => warn!("Skipping invalid history line {}: {}", index + 1, err),
+        }
+    }
+    Ok(entries)
+}
+
+fn history_file(data_dir: &PathBuf) -> PathBuf {
+    let mut file = data_dir.clone();
+    file.push(DYNDNS_HISTORY_FILENAME);
+    file
+}
+
+fn parse_since(input: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(input) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(input, "%Y-%m-%d") {
+        return Ok(date.and_time(Default::default()).and_utc());
+    }
+    let duration = parse_duration(input)
+        .map_err(|_| format!("'{}' is not a date, timestamp or duration", input))?;
+    chrono::Duration::from_std(duration)
+        .map(|duration| Utc::now() - duration)
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,103 @@
+use std::{net::IpAddr, time::Duration};
+
+use serde_json::json;
+use tokio::{process::Command, time::timeout};
+
+/// Hooks run while the next update waits, so one that hangs is killed.
+const COMMAND_TIMEOUT: Duration = Duration::from_secs(60);
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs the hooks for a changed ip. Hooks are best effort, so failures are logged without failing
+/// the update that triggered them.
+pub async fn run_hooks(
+    commands: &[String],
+    webhooks: &[String],
+    host: &str,
+    old_ip: Option<IpAddr>,
+    new_ip: IpAddr,
+) {
+    for command in commands {
+        run_command(command, host, old_ip, new_ip).await;
+    }
+    for url in webhooks {
+        post_webhook(url, host, old_ip, new_ip).await;
+    }
+}
+
+/// Runs the command through the shell with `HOST`, `OLD_IP` and `NEW_IP` set. `OLD_IP` is empty
+/// when there is no previous ip. The command is killed when it runs longer than
+/// `COMMAND_TIMEOUT`.
+async fn run_command(command: &str, host: &str, old_ip: Option<IpAddr>, new_ip: IpAddr) {
+    info!("Running hook '{}'", command);
+
+    let mut process = if cfg!(windows) {
+        let mut process = Command::new("cmd");
+        process.arg("/C");
+        process
+    } else {
+        let mut process = Command::new("sh");
+        process.arg("-c");
+        process
+    };
+    let child = process
+        .arg(command)
+        .env("HOST", host)
+        .env(
+            "OLD_IP",
+            old_ip.map(|ip| ip.to_string()).unwrap_or_default(),
+        )
+        .env("NEW_IP", new_ip.to_string())
+        .kill_on_drop(true)
+        .spawn();
+    let mut child = match child {
+        Ok(child) => child,
+        Err(err) => {
+            warn!("Could not run hook '{}': {}", command, err);
+            return;
+        }
+    };
+
+    let result = match timeout(COMMAND_TIMEOUT, child.wait()).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!(
+                "Hook '{}' did not finish within {} seconds, killing it",
+                command,
+                COMMAND_TIMEOUT.as_secs()
+            );
+            if let Err(err) = child.kill().await {
+                warn!("Could not kill hook '{}': {}", command, err);
+            }
+            return;
+        }
+    };
+
+    match result {
+        Ok(status) if status.success() => debug!("Hook '{}' succeeded", command),
+        Ok(status) => warn!("Hook '{}' exited with {}", command, status),
+        Err(err) => warn!("Could not wait for hook '{}': {}", command, err),
+    }
+}
+
+async fn post_webhook(url: &str, host: &str, old_ip: Option<IpAddr>, new_ip: IpAddr) {
+    info!("Posting webhook to {}", url);
+
+    let body = json!({
+        "host": host,
+        "old_ip": old_ip,
+        "new_ip": new_ip,
+        "timestamp": chrono::Utc::now().to_rfc3339(),
+    });
+    let result = reqwest::Client::new()
+        .post(url)
+        .timeout(WEBHOOK_TIMEOUT)
+        .json(&body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status());
+
+    match result {
+        Ok(_) => debug!("Webhook to {} succeeded", url),
+        Err(err) => warn!("Webhook to {} failed: {}", url, err),
+    }
+}
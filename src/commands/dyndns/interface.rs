@@ -0,0 +1,89 @@
+use std::{
+    fs,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+};
+
+use super::{
+    resolvers::{family_name, matches_family},
+    IpFamily,
+};
+
+// Address flags from linux/if_addr.h
+const IFA_F_TEMPORARY: u32 = 0x01;
+const IFA_F_DEPRECATED: u32 = 0x20;
+
+/// Picks the first address on the interface that is reachable from the internet. Private, shared
+/// (CGNAT), link-local, unique local and temporary privacy addresses are skipped, since they either
+/// can't be reached from the outside or change too often to be put in DNS.
+pub fn interface_address(name: &str, family: Option<IpFamily>) -> Result<IpAddr, String> {
+    let interfaces = if_addrs::get_if_addrs().map_err(|err| err.to_string())?;
+    let short_lived = short_lived_ipv6_addresses(name);
+
+    let mut addresses = interfaces
+        .iter()
+        .filter(|interface| interface.name == name)
+        .map(|interface| interface.ip())
+        .peekable();
+    if addresses.peek().is_none() {
+        return Err(format!("no interface named {}", name));
+    }
+
+    addresses
+        .filter(|ip| matches_family(*ip, family))
+        .find(|ip| match ip {
+            IpAddr::V4(ip) => is_global_ipv4(ip),
+            IpAddr::V6(ip) => is_global_ipv6(ip) && !short_lived.contains(ip),
+        })
+        .ok_or(format!(
+            "no usable {} address on {}",
+            family_name(family),
+            name
+        ))
+}
+
+fn is_global_ipv4(ip: &Ipv4Addr) -> bool {
+    let [first, second, ..] = ip.octets();
+    let shared = first == 100 && second & 0xc0 == 64;
+
+    !ip.is_loopback()
+        && !ip.is_unspecified()
+        && !ip.is_link_local()
+        && !ip.is_private()
+        && !ip.is_broadcast()
+        && !ip.is_multicast()
+        && !shared
+}
+
+fn is_global_ipv6(ip: &Ipv6Addr) -> bool {
+    let first_segment = ip.segments()[0];
+    let link_local = first_segment & 0xffc0 == 0xfe80;
+    let unique_local = first_segment & 0xfe00 == 0xfc00;
+
+    !ip.is_loopback() && !ip.is_unspecified() && !ip.is_multicast() && !link_local && !unique_local
+}
+
+/// Reads temporary and deprecated addresses from `/proc/net/if_inet6`, where each line holds the
+/// address, interface index, prefix length, scope, flags and interface name. Other platforms
+/// don't expose the flags, so no addresses are excluded there.
+fn short_lived_ipv6_addresses(name: &str) -> Vec<Ipv6Addr> {
+    let Ok(content) = fs::read_to_string("/proc/net/if_inet6") else {
+        debug!("Could not read ipv6 address flags");
+        return Vec::new();
+    };
+
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            match fields[..] {
+                [address, _, _, _, flags, interface] if interface == name => {
+                    let address = u128::from_str_radix(address, 16).ok()?;
+                    let flags = u32::from_str_radix(flags, 16).ok()?;
+                    (flags & (IFA_F_TEMPORARY | IFA_F_DEPRECATED) != 0)
+                        .then(|| Ipv6Addr::from(address))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,502 @@
+use std::{
+    fmt::{self, Display},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    time::Duration,
+};
+
+use tokio::{net::UdpSocket, time::timeout};
+
+use super::{interface::interface_address, IpFamily};
+
+const RESOLVER_TIMEOUT: Duration = Duration::from_secs(10);
+const OPENDNS_V4: Ipv4Addr = Ipv4Addr::new(208, 67, 222, 222);
+const OPENDNS_V6: Ipv6Addr = Ipv6Addr::new(0x2620, 0x119, 0x35, 0, 0, 0, 0, 0x35);
+const OPENDNS_MYIP: &str = "myip.opendns.com";
+const NATPMP_PORT: u16 = 5351;
+const SSDP_ADDRESS: (Ipv4Addr, u16) = (Ipv4Addr::new(239, 255, 255, 250), 1900);
+const UPNP_GATEWAY: &str = "urn:schemas-upnp-org:device:InternetGatewayDevice:1";
+const UPNP_SERVICES: &[&str] = &[
+    "urn:schemas-upnp-org:service:WANIPConnection:2",
+    "urn:schemas-upnp-org:service:WANIPConnection:1",
+    "urn:schemas-upnp-org:service:WANPPPConnection:1",
+];
+
+/// A way of finding the public ip of this machine, given on the command line as `public`,
+/// `http(s)://<url>`, `opendns`, `interface:<name>`, `upnp`, `natpmp` or `fixed:<ip>`.
+#[derive(Clone, Debug)]
+pub enum IpSource {
+    Public,
+    Http(String),
+    OpenDns,
+    Interface(String),
+    Upnp,
+    NatPmp,
+    Fixed(IpAddr),
+}
+
+impl FromStr for IpSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = match s.split_once(':') {
+            Some((kind, value)) => (kind, Some(value)),
+            None => (s, None),
+        };
+
+        match (kind, value) {
+            ("public", None) => Ok(IpSource::Public),
+            ("opendns", None) => Ok(IpSource::OpenDns),
+            ("upnp", None) => Ok(IpSource::Upnp),
+            ("natpmp", None) => Ok(IpSource::NatPmp),
+            ("http" | "https", Some(_)) => Ok(IpSource::Http(s.to_string())),
+            ("interface", Some(name)) if !name.is_empty() => {
+                Ok(IpSource::Interface(name.to_string()))
+            }
+            ("fixed", Some(ip)) => ip
+                .parse()
+                .map(IpSource::Fixed)
+                .map_err(|err| format!("Invalid ip '{}': {}", ip, err)),
+            _ => Err(format!(
+                "Unknown ip source '{}'. Use public, http(s)://<url>, opendns, interface:<name>, upnp, natpmp or fixed:<ip>",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for IpSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IpSource::Public => write!(f, "public"),
+            IpSource::Http(url) => write!(f, "{}", url),
+            IpSource::OpenDns => write!(f, "opendns"),
+            IpSource::Interface(name) => write!(f, "interface:{}", name),
+            IpSource::Upnp => write!(f, "upnp"),
+            IpSource::NatPmp => write!(f, "natpmp"),
+            IpSource::Fixed(ip) => write!(f, "fixed:{}", ip),
+        }
+    }
+}
+
+/// Asks every source in order. Sources that fail are skipped, but all sources that answer must
+/// agree, and at least `quorum` of them must answer. Returns the ip along with the sources that
+/// answered.
+///
+/// Without a family, the first answer decides it, so a dual-stack host doesn't compare the IPv6
+/// address of one source with the IPv4 address of another.
+pub async fn resolve(
+    sources: &[IpSource],
+    family: Option<IpFamily>,
+    quorum: usize,
+) -> Result<(IpAddr, String), String> {
+    let mut family = family;
+    let mut answers: Vec<(IpAddr, &IpSource)> = Vec::new();
+
+    for source in sources {
+        match resolve_source(source, family).await {
+            Ok(ip) => {
+                debug!("{} resolved ip {}", source, ip);
+                if let Some((first_ip, first_source)) = answers.first() {
+                    if *first_ip != ip {
+                        return Err(format!(
+                            "{} resolved {} but {} resolved {}",
+                            first_source, first_ip, source, ip
+                        ));
+                    }
+                }
+                answers.push((ip, source));
+                family = family.or(Some(family_of(ip)));
+            }
+            Err(err) => warn!("{} could not resolve ip: {}", source, err),
+        }
+    }
+
+    match answers.first() {
+        Some((ip, _)) if answers.len() >= quorum => Ok((
+            *ip,
+            answers
+                .iter()
+                .map(|(_, source)| source.to_string())
+                .collect::<Vec<String>>()
+                .join(","),
+        )),
+        _ => Err(format!(
+            "{} of the required {} sources resolved an ip",
+            answers.len(),
+            quorum
+        )),
+    }
+}
+
+async fn resolve_source(source: &IpSource, family: Option<IpFamily>) -> Result<IpAddr, String> {
+    let ip = match source {
+        IpSource::Public => resolve_public(family).await,
+        IpSource::Http(url) => resolve_http(url, family).await,
+        IpSource::OpenDns => resolve_opendns(family).await,
+        IpSource::Interface(name) => interface_address(name, family),
+        IpSource::Upnp => resolve_upnp(family).await,
+        IpSource::NatPmp => resolve_natpmp(family).await,
+        IpSource::Fixed(ip) => Ok(*ip),
+    }?;
+
+    if matches_family(ip, family) {
+        Ok(ip)
+    } else {
+        Err(format!("{} is not an {} address", ip, family_name(family)))
+    }
+}
+
+async fn resolve_public(family: Option<IpFamily>) -> Result<IpAddr, String> {
+    let ip = match family {
+        None => public_ip::addr().await,
+        Some(IpFamily::V4) => public_ip::addr_v4().await.map(IpAddr::V4),
+        Some(IpFamily::V6) => public_ip::addr_v6().await.map(IpAddr::V6),
+    };
+    ip.ok_or("no answer".to_string())
+}
+
+/// Binding the client to the unspecified address of a family makes the request, and therefore
+/// the address echoed back, use that family.
+async fn resolve_http(url: &str, family: Option<IpFamily>) -> Result<IpAddr, String> {
+    let mut builder = reqwest::Client::builder().timeout(RESOLVER_TIMEOUT);
+    builder = match family {
+        None => builder,
+        Some(IpFamily::V4) => builder.local_address(IpAddr::V4(Ipv4Addr::UNSPECIFIED)),
+        Some(IpFamily::V6) => builder.local_address(IpAddr::V6(Ipv6Addr::UNSPECIFIED)),
+    };
+    let client = builder.build().map_err(|err| err.to_string())?;
+
+    let body = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?
+        .text()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    body.trim()
+        .parse()
+        .map_err(|_| format!("response '{}' is not an ip", body.trim()))
+}
+
+/// OpenDNS answers queries for myip.opendns.com with the address the query came from.
+async fn resolve_opendns(family: Option<IpFamily>) -> Result<IpAddr, String> {
+    let (server, query_type) = match family {
+        Some(IpFamily::V6) => (IpAddr::V6(OPENDNS_V6), 28),
+        _ => (IpAddr::V4(OPENDNS_V4), 1),
+    };
+    let bind: SocketAddr = match server {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+
+    let id = (chrono::Utc::now().timestamp_subsec_nanos() & 0xffff) as u16;
+    let query = dns_query(id, OPENDNS_MYIP, query_type);
+
+    let socket = UdpSocket::bind(bind).await.map_err(|err| err.to_string())?;
+    socket
+        .send_to(&query, (server, 53))
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut response = [0u8; 512];
+    let length = timeout(RESOLVER_TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| "timed out".to_string())?
+        .map_err(|err| err.to_string())?;
+
+    parse_dns_answer(&response[..length], id, query_type)
+}
+
+fn dns_query(id: u16, name: &str, query_type: u16) -> Vec<u8> {
+    let mut query = Vec::with_capacity(32);
+    query.extend(id.to_be_bytes());
+    // Recursion desired, one question
+    query.extend([0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+    for label in name.split('.') {
+        query.push(label.len() as u8);
+        query.extend(label.as_bytes());
+    }
+    query.push(0);
+    query.extend(query_type.to_be_bytes());
+    query.extend(1u16.to_be_bytes());
+    query
+}
+
+fn parse_dns_answer(response: &[u8], id: u16, query_type: u16) -> Result<IpAddr, String> {
+    let read_u16 = |offset: usize| -> Result<u16, String> {
+        response
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or("truncated response".to_string())
+    };
+
+    if read_u16(0)? != id {
+        return Err("response id does not match query".to_string());
+    }
+    let rcode = read_u16(2)? & 0x000f;
+    if rcode != 0 {
+        return Err(format!("server answered with error code {}", rcode));
+    }
+    let questions = read_u16(4)?;
+    let answers = read_u16(6)?;
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = skip_name(response, offset)? + 4;
+    }
+    for _ in 0..answers {
+        offset = skip_name(response, offset)?;
+        let record_type = read_u16(offset)?;
+        let length = read_u16(offset + 8)? as usize;
+        let data = response
+            .get(offset + 10..offset + 10 + length)
+            .ok_or("truncated response")?;
+        offset += 10 + length;
+
+        if record_type != query_type {
+            continue;
+        }
+        if let Ok(bytes) = <[u8; 4]>::try_from(data) {
+            return Ok(IpAddr::from(bytes));
+        }
+        if let Ok(bytes) = <[u8; 16]>::try_from(data) {
+            return Ok(IpAddr::from(bytes));
+        }
+    }
+
+    Err("no address in response".to_string())
+}
+
+fn skip_name(response: &[u8], mut offset: usize) -> Result<usize, String> {
+    loop {
+        let length = *response.get(offset).ok_or("truncated response")?;
+        match length {
+            0 => return Ok(offset + 1),
+            // Compressed names end with a two byte pointer
+            _ if length & 0xc0 == 0xc0 => return Ok(offset + 2),
+            _ => offset += 1 + length as usize,
+        }
+    }
+}
+
+/// Finds the router with SSDP and asks its WAN connection service for the external address, as
+/// described by the UPnP Internet Gateway Device specification. Only IPv4 is supported.
+async fn resolve_upnp(family: Option<IpFamily>) -> Result<IpAddr, String> {
+    if let Some(IpFamily::V6) = family {
+        return Err("UPnP only supports IPv4".to_string());
+    }
+
+    let location = discover_gateway().await?;
+    let client = reqwest::Client::builder()
+        .timeout(RESOLVER_TIMEOUT)
+        .build()
+        .map_err(|err| err.to_string())?;
+    let description = client
+        .get(location.clone())
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?
+        .text()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let (service, control_url) = UPNP_SERVICES
+        .iter()
+        .find_map(|service| Some((*service, control_url(&description, service)?)))
+        .ok_or("gateway has no WAN connection service".to_string())?;
+    let control_url = location
+        .join(&control_url)
+        .map_err(|err| format!("invalid control url: {}", err))?;
+
+    let body = format!(
+        "<?xml version=\"1.0\"?>\
+         <s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" \
+         s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\">\
+         <s:Body><u:GetExternalIPAddress xmlns:u=\"{}\"/></s:Body></s:Envelope>",
+        service
+    );
+    let response = client
+        .post(control_url)
+        .header("Content-Type", "text/xml; charset=\"utf-8\"")
+        .header(
+            "SOAPAction",
+            format!("\"{}#GetExternalIPAddress\"", service),
+        )
+        .body(body)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?
+        .text()
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let ip = xml_value(&response, "NewExternalIPAddress")
+        .ok_or("no external address in response".to_string())?;
+    ip.parse()
+        .map_err(|_| format!("gateway answered '{}', which is not an ip", ip))
+}
+
+/// Sends an SSDP search and returns the description url of the first gateway that answers.
+async fn discover_gateway() -> Result<reqwest::Url, String> {
+    let search = format!(
+        "M-SEARCH * HTTP/1.1\r\nHOST: {}:{}\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: {}\r\n\r\n",
+        SSDP_ADDRESS.0, SSDP_ADDRESS.1, UPNP_GATEWAY
+    );
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .map_err(|err| err.to_string())?;
+    socket
+        .send_to(search.as_bytes(), SSDP_ADDRESS)
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut response = [0u8; 2048];
+    let length = timeout(RESOLVER_TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| "no gateway answered the UPnP search".to_string())?
+        .map_err(|err| err.to_string())?;
+
+    String::from_utf8_lossy(&response[..length])
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.trim()
+                .eq_ignore_ascii_case("location")
+                .then(|| value.trim().to_string())
+        })
+        .ok_or("gateway answer has no location".to_string())?
+        .parse()
+        .map_err(|err| format!("invalid gateway location: {}", err))
+}
+
+/// The control url of a service is the `controlURL` following its `serviceType` in the device
+/// description.
+fn control_url(description: &str, service: &str) -> Option<String> {
+    let service_type = format!("<serviceType>{}</serviceType>", service);
+    let start = description.find(&service_type)?;
+    let service = &description[start..];
+    let service = &service[..service.find("</service>").unwrap_or(service.len())];
+    xml_value(service, "controlURL")
+}
+
+fn xml_value(xml: &str, element: &str) -> Option<String> {
+    let open = format!("<{}>", element);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&format!("</{}>", element))?;
+    Some(xml[start..end].trim().to_string())
+}
+
+/// Asks the default gateway for its external address using NAT-PMP (RFC 6886), which only
+/// supports IPv4.
+async fn resolve_natpmp(family: Option<IpFamily>) -> Result<IpAddr, String> {
+    if let Some(IpFamily::V6) = family {
+        return Err("NAT-PMP only supports IPv4".to_string());
+    }
+    let gateway = default_gateway()?;
+
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .map_err(|err| err.to_string())?;
+    socket
+        .send_to(&[0, 0], (gateway, NATPMP_PORT))
+        .await
+        .map_err(|err| err.to_string())?;
+
+    let mut response = [0u8; 16];
+    let length = timeout(RESOLVER_TIMEOUT, socket.recv(&mut response))
+        .await
+        .map_err(|_| "timed out".to_string())?
+        .map_err(|err| err.to_string())?;
+
+    match &response[..length] {
+        [0, 128, 0, 0, _, _, _, _, a, b, c, d] => Ok(IpAddr::V4(Ipv4Addr::new(*a, *b, *c, *d))),
+        [0, 128, code_high, code_low, ..] => Err(format!(
+            "gateway answered with result code {}",
+            u16::from_be_bytes([*code_high, *code_low])
+        )),
+        _ => Err("unexpected response from gateway".to_string()),
+    }
+}
+
+/// Reads the default route from the kernel routing table, where addresses are stored as little
+/// endian hex.
+#[cfg(target_os = "linux")]
+fn default_gateway() -> Result<Ipv4Addr, String> {
+    let routes = std::fs::read_to_string("/proc/net/route")
+        .map_err(|err| format!("could not read routing table: {}", err))?;
+
+    routes
+        .lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect::<Vec<&str>>())
+        .find(|fields| fields.get(1) == Some(&"00000000"))
+        .and_then(|fields| u32::from_str_radix(fields.get(2)?, 16).ok())
+        .map(|gateway| Ipv4Addr::from(gateway.to_le_bytes()))
+        .ok_or("no default gateway found".to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn default_gateway() -> Result<Ipv4Addr, String> {
+    Err("finding the default gateway for NAT-PMP is only supported on Linux, use upnp".to_string())
+}
+
+pub(super) fn matches_family(ip: IpAddr, family: Option<IpFamily>) -> bool {
+    matches!(
+        (ip, family),
+        (_, None) | (IpAddr::V4(_), Some(IpFamily::V4)) | (IpAddr::V6(_), Some(IpFamily::V6))
+    )
+}
+
+fn family_of(ip: IpAddr) -> IpFamily {
+    match ip {
+        IpAddr::V4(_) => IpFamily::V4,
+        IpAddr::V6(_) => IpFamily::V6,
+    }
+}
+
+pub(super) fn family_name(family: Option<IpFamily>) -> &'static str {
+    match family {
+        None => "ip",
+        Some(IpFamily::V4) => "IPv4",
+        Some(IpFamily::V6) => "IPv6",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_control_url_of_wan_service() {
+        let description = "<root><device><serviceList>\
+            <service><serviceType>urn:schemas-upnp-org:service:Layer3Forwarding:1</serviceType>\
+            <controlURL>/l3f</controlURL></service>\
+            <service><serviceType>urn:schemas-upnp-org:service:WANIPConnection:1</serviceType>\
+            <controlURL> /ctl/IPConn </controlURL></service>\
+            </serviceList></device></root>";
+
+        assert_eq!(
+            control_url(description, UPNP_SERVICES[1]),
+            Some("/ctl/IPConn".to_string())
+        );
+        assert_eq!(control_url(description, UPNP_SERVICES[0]), None);
+    }
+
+    #[test]
+    fn reads_external_address() {
+        let response = "<s:Envelope><s:Body><u:GetExternalIPAddressResponse>\
+            <NewExternalIPAddress>198.51.100.7</NewExternalIPAddress>\
+            </u:GetExternalIPAddressResponse></s:Body></s:Envelope>";
+
+        assert_eq!(
+            xml_value(response, "NewExternalIPAddress"),
+            Some("198.51.100.7".to_string())
+        );
+    }
+}
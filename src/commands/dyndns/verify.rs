@@ -0,0 +1,30 @@
+use std::net::IpAddr;
+
+use domeneshop_client::{client::DomeneshopClient, endpoints::dns::DnsType};
+
+use crate::domain_lookup::find_domain_for_hostname;
+
+/// Checks whether the A or AAAA record of the hostname currently points to the ip. Returns
+/// `None` when the records can't be fetched, so the caller can decide how to proceed.
+pub async fn record_matches(client: &DomeneshopClient, hostname: &str, ip: IpAddr) -> Option<bool> {
+    let (domain_id, host) = find_domain_for_hostname(hostname, client).await?;
+    let dns_type = match ip {
+        IpAddr::V4(_) => DnsType::A,
+        IpAddr::V6(_) => DnsType::AAAA,
+    };
+
+    match client
+        .list_dns_records_with_filter(domain_id, Some(host), Some(dns_type))
+        .await
+    {
+        Ok(records) => Some(
+            records
+                .iter()
+                .any(|record| record.data.data.parse::<IpAddr>() == Ok(ip)),
+        ),
+        Err(err) => {
+            warn!("Could not fetch dns records for {}: {}", hostname, err);
+            None
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use std::{path::PathBuf, process::ExitCode, time::Duration};
+
+use domeneshop_client::client::DomeneshopClient;
+
+use super::{update_all, Command};
+use crate::{log_and_fail_with_error, shutdown::Shutdown};
+
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+pub async fn watch(
+    command: &Command,
+    hosts: &[String],
+    client: &DomeneshopClient,
+    data_dir: &PathBuf,
+) -> ExitCode {
+    let mut shutdown = match Shutdown::listen() {
+        Ok(shutdown) => shutdown,
+        Err(err) => return log_and_fail_with_error("Could not listen for shutdown signals", err),
+    };
+    info!("Watching public ip every {:?}", command.interval);
+
+    let mut failures = 0;
+    loop {
+        let delay = if update_all(command, hosts, client, data_dir).await == ExitCode::SUCCESS {
+            failures = 0;
+            command.interval
+        } else {
+            failures += 1;
+            let delay = retry_delay(failures, command.interval);
+            warn!(
+                "Update failed {} times in a row, retrying in {:?}",
+                failures, delay
+            );
+            delay
+        };
+
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => (),
+            _ = shutdown.wait() => {
+                info!("Received shutdown signal, stopping");
+                return ExitCode::SUCCESS;
+            }
+        }
+    }
+}
+
+/// Doubles the delay for every consecutive failure, but never waits longer than a regular check.
+fn retry_delay(failures: u32, interval: Duration) -> Duration {
+    FIRST_RETRY_DELAY
+        .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+        .min(interval)
+}
@@ -1,20 +1,30 @@
 use std::process::ExitCode;
 
 use clap::*;
+use clap_complete::ArgValueCandidates;
 use domeneshop_client::{
     client::DomeneshopClient,
     endpoints::{domains::DomainId, forwards::HttpForward},
 };
+use serde_json::{json, Value};
 
 use crate::{
+    completion::domain_candidates,
     domain_lookup::{get_domain_id, DomainIdOrHost},
     log_and_fail, log_and_fail_with_error,
+    output::{Output, Render},
+    prompt::{can_prompt, confirm},
 };
 
 #[derive(Parser)]
 pub struct ForwardArgs {
-    #[arg(short, long, help = "Id or name of the domain to manage forwards for")]
-    domain: DomainIdOrHost,
+    #[arg(
+        short,
+        long,
+        add = ArgValueCandidates::new(domain_candidates),
+        help = "Id or name of the domain to manage forwards for. Defaults to domain in config.toml"
+    )]
+    domain: Option<DomainIdOrHost>,
     #[command(subcommand)]
     command: Command,
 }
@@ -23,6 +33,9 @@ pub struct ForwardArgs {
 pub enum Command {
     List,
     Get(GetForwardArgs),
+    Add(AddForwardArgs),
+    Update(UpdateForwardArgs),
+    Delete(DeleteForwardArgs),
 }
 
 // #[derive(Parser)]
@@ -33,6 +46,41 @@ pub struct GetForwardArgs {
     host: String,
 }
 
+#[derive(Parser)]
+pub struct AddForwardArgs {
+    #[arg(help = "Host to forward from. Use @ for the domain itself")]
+    host: String,
+    #[arg(help = "Url to forward to")]
+    url: String,
+    #[arg(
+        long,
+        action,
+        help = "Shows the target in a frame instead of redirecting to it"
+    )]
+    frame: bool,
+}
+
+#[derive(Parser)]
+pub struct UpdateForwardArgs {
+    host: String,
+    #[arg(long, help = "New url to forward to")]
+    url: Option<String>,
+    #[arg(long, help = "Whether to show the target in a frame")]
+    frame: Option<bool>,
+}
+
+#[derive(Parser)]
+pub struct DeleteForwardArgs {
+    host: String,
+    #[arg(
+        short,
+        long,
+        action,
+        help = "Deletes the forward without asking for confirmation"
+    )]
+    yes: bool,
+}
+
 #[derive(ValueEnum, Clone, Debug)]
 enum DnsTypeArg {
     A,
@@ -43,32 +91,50 @@ enum DnsTypeArg {
     TXT,
 }
 
-pub async fn handle_forwards(args: &ForwardArgs, client: &DomeneshopClient) -> ExitCode {
-    match get_domain_id(&args.domain, client).await {
+pub async fn handle_forwards(
+    args: &ForwardArgs,
+    client: &DomeneshopClient,
+    default_domain: Option<&DomainIdOrHost>,
+    output: &Output,
+) -> ExitCode {
+    let Some(domain) = args.domain.as_ref().or(default_domain) else {
+        return log_and_fail("No domain given. Use --domain or set domain in config.toml");
+    };
+    match get_domain_id(domain, client).await {
         Some(domain_id) => match &args.command {
-            Command::List => list_forwards(client, domain_id).await,
-            Command::Get(args) => get_forward(client, domain_id, &args.host).await,
+            Command::List => list_forwards(client, domain_id, output).await,
+            Command::Get(args) => get_forward(client, domain_id, &args.host, output).await,
+            Command::Add(args) => add_forward(client, domain_id, args).await,
+            Command::Update(args) => update_forward(client, domain_id, args).await,
+            Command::Delete(args) => delete_forward(client, domain_id, args, output).await,
         },
         None => log_and_fail("Could not resolve --domain input to a domain"),
     }
 }
 
-async fn get_forward(client: &DomeneshopClient, domain_id: DomainId, host: &String) -> ExitCode {
+async fn get_forward(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    host: &String,
+    output: &Output,
+) -> ExitCode {
     info!(
         "Getting forward with host {} for domain {}",
         host, domain_id
     );
 
     match client.get_forward(domain_id, host.clone()).await {
-        Ok(forward) => {
-            print_forward(forward);
-            ExitCode::SUCCESS
-        }
+        Ok(Some(forward)) => output.item(&forward),
+        Ok(None) => log_and_fail(format!("Forward for host {} not found", host)),
         Err(err) => log_and_fail_with_error("Failed to get forward", err),
     }
 }
 
-async fn list_forwards(client: &DomeneshopClient, domain_id: DomainId) -> ExitCode {
+async fn list_forwards(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    output: &Output,
+) -> ExitCode {
     info!("Listing forwards ...");
 
     let response = client.list_forwards(domain_id).await;
@@ -76,21 +142,119 @@ async fn list_forwards(client: &DomeneshopClient, domain_id: DomainId) -> ExitCo
     match response {
         Err(err) => log_and_fail_with_error("Failed to list forwards", err),
         Ok(forwards) => {
-            println!("Got {} forwards.", forwards.len());
-            for forward in forwards {
-                println!("{} -> {}", forward.host, forward.url);
-            }
+            info!("Got {} forwards", forwards.len());
+            output.list(&forwards)
+        }
+    }
+}
+
+async fn add_forward(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &AddForwardArgs,
+) -> ExitCode {
+    info!(
+        "Adding forward from {} to {} for domain {}",
+        args.host, args.url, domain_id
+    );
+
+    let forward = HttpForward {
+        host: args.host.clone(),
+        frame: args.frame,
+        url: args.url.clone(),
+    };
+
+    match client.add_forward(domain_id, forward).await {
+        Ok(_) => {
+            info!("Added forward for host {}", args.host);
+            println!("Added forward for host {}", args.host);
+            ExitCode::SUCCESS
+        }
+        Err(err) => log_and_fail_with_error("Failed to add forward", err),
+    }
+}
+
+async fn update_forward(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &UpdateForwardArgs,
+) -> ExitCode {
+    info!(
+        "Updating forward with host {} for domain {}",
+        args.host, domain_id
+    );
+
+    let existing = match client.get_forward(domain_id, args.host.clone()).await {
+        Ok(Some(forward)) => forward,
+        Ok(None) => return log_and_fail(format!("Forward for host {} not found", args.host)),
+        Err(err) => return log_and_fail_with_error("Failed to get forward", err),
+    };
+
+    let forward = HttpForward {
+        host: existing.host,
+        frame: args.frame.unwrap_or(existing.frame),
+        url: args.url.clone().unwrap_or(existing.url),
+    };
+
+    match client
+        .update_forward(domain_id, args.host.clone(), forward)
+        .await
+    {
+        Ok(_) => {
+            info!("Updated forward for host {}", args.host);
+            println!("Updated forward for host {}", args.host);
             ExitCode::SUCCESS
         }
+        Err(err) => log_and_fail_with_error("Failed to update forward", err),
     }
 }
 
-fn print_forward(forward: Option<HttpForward>) {
-    match forward {
-        Some(forward) => println!(
-            "{} -> {} (frame: {})",
-            forward.host, forward.url, forward.frame
-        ),
-        None => println!("Forward not found"),
+async fn delete_forward(
+    client: &DomeneshopClient,
+    domain_id: DomainId,
+    args: &DeleteForwardArgs,
+    output: &Output,
+) -> ExitCode {
+    info!(
+        "Deleting forward with host {} for domain {}",
+        args.host, domain_id
+    );
+
+    if !args.yes {
+        if !can_prompt() {
+            return log_and_fail("Not deleting without confirmation. Use --yes to delete anyway");
+        }
+        let shown = match client.get_forward(domain_id, args.host.clone()).await {
+            Ok(Some(forward)) => output.item(&forward),
+            Ok(None) => return log_and_fail(format!("Forward for host {} not found", args.host)),
+            Err(err) => return log_and_fail_with_error("Failed to get forward", err),
+        };
+        if shown != ExitCode::SUCCESS {
+            return shown;
+        }
+        if !confirm("Delete this forward?") {
+            return log_and_fail("Aborted, the forward was not deleted");
+        }
+    }
+
+    match client.delete_forward(domain_id, args.host.clone()).await {
+        Ok(_) => {
+            info!("Deleted forward for host {}", args.host);
+            println!("Deleted forward for host {}", args.host);
+            ExitCode::SUCCESS
+        }
+        Err(err) => log_and_fail_with_error("Failed to delete forward", err),
+    }
+}
+
+impl Render for HttpForward {
+    const DEFAULT_COLUMNS: &'static [&'static str] = &["host", "url", "frame"];
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("host", json!(self.host)),
+            ("url", json!(self.url.to_string())),
+            ("frame", json!(self.frame)),
+        ]
     }
 }
@@ -1,12 +1,21 @@
-use std::process::ExitCode;
+use std::{fs, path::PathBuf, process::ExitCode, time::Duration};
 
+use chrono::NaiveDate;
 use clap::*;
 use domeneshop_client::{
     client::DomeneshopClient,
     endpoints::invoices::{Invoice, InvoiceId, InvoiceStatus},
 };
 
-use crate::log_and_fail_with_error;
+use serde_json::{json, Value};
+
+use crate::{
+    log_and_fail, log_and_fail_with_error,
+    output::{Output, Render},
+};
+
+/// Applies to each invoice, so a stalled server can't hang a scheduled download.
+const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(60);
 
 #[derive(Parser)]
 pub struct InvoiceArgs {
@@ -18,6 +27,8 @@ pub struct InvoiceArgs {
 pub enum Command {
     List(ListInvoiceArgs),
     Get(GetInvoiceArgs),
+    /// Saves invoices as <issued_date>_<id>.pdf
+    Download(DownloadInvoiceArgs),
 }
 
 #[derive(Parser)]
@@ -31,6 +42,26 @@ pub struct GetInvoiceArgs {
     id: InvoiceId,
 }
 
+#[derive(Parser)]
+#[command(group(ArgGroup::new("selection").args(["id", "status", "since"]).required(true).multiple(true)))]
+pub struct DownloadInvoiceArgs {
+    #[arg(
+        conflicts_with_all = ["status", "since"],
+        help = "Invoice to download. Use --status and --since to download several"
+    )]
+    id: Option<InvoiceId>,
+    #[arg(long, default_value = ".", help = "Directory to save the invoices in")]
+    out: PathBuf,
+    #[arg(short, long, help = "Downloads all invoices with the status")]
+    status: Option<InvoiceStatusInput>,
+    #[arg(
+        long,
+        value_parser = parse_date,
+        help = "Downloads all invoices issued on or after the date, e.g. 2025-01-01"
+    )]
+    since: Option<NaiveDate>,
+}
+
 #[derive(ValueEnum, Clone, Debug)]
 pub enum InvoiceStatusInput {
     Unpaid,
@@ -38,14 +69,23 @@ pub enum InvoiceStatusInput {
     Settled,
 }
 
-pub async fn handle_invoices(args: &InvoiceArgs, client: &DomeneshopClient) -> ExitCode {
+pub async fn handle_invoices(
+    args: &InvoiceArgs,
+    client: &DomeneshopClient,
+    output: &Output,
+) -> ExitCode {
     match &args.command {
-        Command::List(args) => list_invoices(client, args).await,
-        Command::Get(args) => get_invoice(client, args.id).await,
+        Command::List(args) => list_invoices(client, args, output).await,
+        Command::Get(args) => get_invoice(client, args.id, output).await,
+        Command::Download(args) => download_invoices(client, args).await,
     }
 }
 
-async fn list_invoices(client: &DomeneshopClient, args: &ListInvoiceArgs) -> ExitCode {
+async fn list_invoices(
+    client: &DomeneshopClient,
+    args: &ListInvoiceArgs,
+    output: &Output,
+) -> ExitCode {
     info!("Listing invoices with status {:?} ...", args.status);
 
     let response = match &args.status {
@@ -56,35 +96,118 @@ async fn list_invoices(client: &DomeneshopClient, args: &ListInvoiceArgs) -> Exi
     match response {
         Err(err) => log_and_fail_with_error("Failed to list invoices", err),
         Ok(invoices) => {
-            println!("Got {} invoices.", invoices.len());
-            println!("");
-            for invoice in invoices {
-                println!(
-                    "{}: {} {} ({})",
-                    invoice.id, invoice.amount, invoice.currency, invoice.status
-                );
-                println!("");
-            }
-            ExitCode::SUCCESS
+            info!("Got {} invoices", invoices.len());
+            output.list(&invoices)
         }
     }
 }
 
-async fn get_invoice(client: &DomeneshopClient, id: InvoiceId) -> ExitCode {
+async fn get_invoice(client: &DomeneshopClient, id: InvoiceId, output: &Output) -> ExitCode {
     info!("Getting invoice with id {}", id);
 
     match client.get_invoice(id).await {
-        Ok(response) => {
-            match response {
-                Some(invoice) => {
-                    print_invoice(&invoice);
-                }
-                None => println!("Invoice {} not found", id),
+        Ok(response) => match response {
+            Some(invoice) => output.item(&invoice),
+            None => log_and_fail(format!("Invoice {} not found", id)),
+        },
+        Err(err) => log_and_fail_with_error("Failed to get invoice", err),
+    }
+}
+
+async fn download_invoices(client: &DomeneshopClient, args: &DownloadInvoiceArgs) -> ExitCode {
+    let invoices = match args.id {
+        Some(id) => match client.get_invoice(id).await {
+            Ok(Some(invoice)) => vec![invoice],
+            Ok(None) => return log_and_fail(format!("Invoice {} not found", id)),
+            Err(err) => return log_and_fail_with_error("Failed to get invoice", err),
+        },
+        None => {
+            let response = match &args.status {
+                Some(status) => client.list_invoices_with_status(map_status(status)).await,
+                None => client.list_invoices().await,
             };
-            ExitCode::SUCCESS
+            match response {
+                Ok(invoices) => invoices
+                    .into_iter()
+                    .filter(|invoice| args.since.is_none_or(|since| invoice.issued_date >= since))
+                    .collect(),
+                Err(err) => return log_and_fail_with_error("Failed to list invoices", err),
+            }
         }
-        Err(err) => log_and_fail_with_error("Failed to get invoice", err),
+    };
+
+    let http = match reqwest::Client::builder().timeout(DOWNLOAD_TIMEOUT).build() {
+        Ok(http) => http,
+        Err(err) => return log_and_fail_with_error("Could not create http client", err),
+    };
+    if let Err(err) = fs::create_dir_all(&args.out) {
+        return log_and_fail_with_error(format!("Could not create {}", args.out.display()), err);
     }
+    info!(
+        "Downloading {} invoices to {} ...",
+        invoices.len(),
+        args.out.display()
+    );
+
+    let mut result = ExitCode::SUCCESS;
+    for invoice in &invoices {
+        let file = args
+            .out
+            .join(format!("{}_{}.pdf", invoice.issued_date, invoice.id));
+        if file.exists() {
+            println!("{} already exists, skipping", file.display());
+            continue;
+        }
+
+        match download_pdf(&http, &invoice.url.to_string(), &file).await {
+            Ok(_) => {
+                info!("Saved invoice {} to {}", invoice.id, file.display());
+                println!("Saved {}", file.display());
+            }
+            Err(err) => {
+                result = log_and_fail(format!(
+                    "Could not download invoice {}: {}",
+                    invoice.id, err
+                ))
+            }
+        }
+    }
+    result
+}
+
+/// Writes to a temporary file first, so an interrupted download never leaves a file that a later
+/// run would skip as already downloaded.
+async fn download_pdf(http: &reqwest::Client, url: &str, file: &PathBuf) -> Result<(), String> {
+    let bytes = http
+        .get(url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|err| err.to_string())?
+        .bytes()
+        .await
+        .map_err(|err| err.to_string())?;
+    if !bytes.starts_with(b"%PDF") {
+        let start = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]).to_lowercase();
+        if start.contains("<html") || start.contains("<!doctype html") {
+            return Err(format!(
+                "{} returned a web page instead of a PDF. The invoice url probably needs a login \
+                 on domene.shop, so download it from the browser",
+                url
+            ));
+        }
+        return Err(format!("{} did not return a PDF", url));
+    }
+
+    let temporary = file.with_extension("pdf.tmp");
+    fs::write(&temporary, &bytes)
+        .and_then(|_| fs::rename(&temporary, file))
+        .map_err(|err| format!("could not write {}: {}", file.display(), err))
+}
+
+fn parse_date(input: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(input, "%Y-%m-%d")
+        .map_err(|_| format!("'{}' is not a date like 2025-01-31", input))
 }
 
 fn map_status(input: &InvoiceStatusInput) -> InvoiceStatus {
@@ -95,17 +218,33 @@ fn map_status(input: &InvoiceStatusInput) -> InvoiceStatus {
     }
 }
 
-fn print_invoice(invoice: &Invoice) {
-    println!("Id: {}", invoice.id);
-    println!("Type: {}", invoice.r#type);
-    println!("Amount: {} {}", invoice.amount, invoice.currency);
-    println!("Status: {}", invoice.status);
-    println!("Issued at {}", invoice.issued_date);
-    if let Some(due_date) = invoice.due_date {
-        println!("Due at {}", due_date);
-    }
-    if let Some(paid_date) = invoice.paid_date {
-        println!("Paid at {}", paid_date);
+impl Render for Invoice {
+    const DEFAULT_COLUMNS: &'static [&'static str] = &[
+        "id",
+        "amount",
+        "currency",
+        "status",
+        "issued_date",
+        "due_date",
+    ];
+
+    fn fields(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("id", json!(self.id)),
+            ("type", json!(self.r#type.to_string())),
+            ("amount", json!(self.amount)),
+            ("currency", json!(self.currency.to_string())),
+            ("status", json!(self.status.to_string())),
+            ("issued_date", json!(self.issued_date.to_string())),
+            (
+                "due_date",
+                json!(self.due_date.as_ref().map(ToString::to_string)),
+            ),
+            (
+                "paid_date",
+                json!(self.paid_date.as_ref().map(ToString::to_string)),
+            ),
+            ("url", json!(self.url.to_string())),
+        ]
     }
-    println!("Url: {}", invoice.url);
 }
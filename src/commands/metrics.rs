@@ -0,0 +1,322 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    net::{IpAddr, SocketAddr},
+    path::PathBuf,
+    process::ExitCode,
+    time::Duration,
+};
+
+use clap::*;
+use domeneshop_client::{client::DomeneshopClient, endpoints::invoices::InvoiceStatus};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+
+use crate::{
+    commands::dyndns::{current_ips, last_updates},
+    log_and_fail_with_error,
+    shutdown::Shutdown,
+};
+
+/// How long a client may take to send its request.
+const READ_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long the API may take to answer before the scrape fails, which should stay below the
+/// scrape timeout of Prometheus.
+const COLLECT_TIMEOUT: Duration = Duration::from_secs(8);
+/// Upper bound for answering a connection, including writing the response.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+#[derive(Parser)]
+#[command(group(ArgGroup::new("target").args(["listen", "textfile"]).required(true)))]
+pub struct MetricsArgs {
+    #[arg(
+        long,
+        value_name = "ADDRESS",
+        help = "Serves metrics over HTTP on the address, e.g. 127.0.0.1:9793"
+    )]
+    listen: Option<SocketAddr>,
+    #[arg(
+        long,
+        value_name = "FILE",
+        help = "Writes metrics to a .prom file for the node_exporter textfile collector"
+    )]
+    textfile: Option<PathBuf>,
+}
+
+pub async fn handle_metrics(
+    args: &MetricsArgs,
+    client: &DomeneshopClient,
+    data_dir: &PathBuf,
+) -> ExitCode {
+    match (&args.listen, &args.textfile) {
+        (Some(address), _) => serve(*address, client, data_dir).await,
+        (None, Some(file)) => write_textfile(file, client, data_dir).await,
+        (None, None) => unreachable!("clap requires --listen or --textfile"),
+    }
+}
+
+/// Writes to a temporary file first, so the collector never reads a half written file.
+async fn write_textfile(file: &PathBuf, client: &DomeneshopClient, data_dir: &PathBuf) -> ExitCode {
+    info!("Writing metrics to {}", file.display());
+    let metrics = collect(client, data_dir).await;
+
+    let temporary = file.with_extension("prom.tmp");
+    match fs::write(&temporary, metrics).and_then(|_| fs::rename(&temporary, file)) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => log_and_fail_with_error(format!("Could not write {}", file.display()), err),
+    }
+}
+
+/// Metrics are collected on every scrape, so they are never older than the scrape interval.
+/// Connections are answered one at a time, each bounded by a timeout, and a shutdown signal
+/// stops the server even while a connection is being answered.
+async fn serve(address: SocketAddr, client: &DomeneshopClient, data_dir: &PathBuf) -> ExitCode {
+    let mut shutdown = match Shutdown::listen() {
+        Ok(shutdown) => shutdown,
+        Err(err) => return log_and_fail_with_error("Could not listen for shutdown signals", err),
+    };
+    let listener = match TcpListener::bind(address).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            return log_and_fail_with_error(format!("Could not listen on {}", address), err)
+        }
+    };
+    info!("Serving metrics on http://{}/metrics", address);
+
+    loop {
+        let (stream, peer) = tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    warn!("Could not accept connection: {}", err);
+                    continue;
+                }
+            },
+            _ = shutdown.wait() => {
+                info!("Received shutdown signal, stopping");
+                return ExitCode::SUCCESS;
+            }
+        };
+
+        tokio::select! {
+            answered = timeout(REQUEST_TIMEOUT, respond(stream, client, data_dir)) => match answered {
+                Ok(Ok(_)) => (),
+                Ok(Err(err)) => warn!("Could not answer {}: {}", peer, err),
+                Err(_) => warn!("Timed out answering {}", peer),
+            },
+            _ = shutdown.wait() => {
+                info!("Received shutdown signal, stopping");
+                return ExitCode::SUCCESS;
+            }
+        }
+    }
+}
+
+async fn respond(
+    mut stream: TcpStream,
+    client: &DomeneshopClient,
+    data_dir: &PathBuf,
+) -> std::io::Result<()> {
+    let mut request = [0u8; 1024];
+    let length = timeout(READ_TIMEOUT, stream.read(&mut request))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "no request received"))??;
+    let request = String::from_utf8_lossy(&request[..length]);
+    let path = request.split_whitespace().nth(1).unwrap_or("/");
+
+    let (status, content_type, body) = match path {
+        "/metrics" => match timeout(COLLECT_TIMEOUT, collect(client, data_dir)).await {
+            Ok(metrics) => ("200 OK", "text/plain; version=0.0.4", metrics),
+            Err(_) => {
+                warn!("Collecting metrics timed out");
+                (
+                    "503 Service Unavailable",
+                    "text/plain",
+                    "Collecting metrics timed out\n".to_string(),
+                )
+            }
+        },
+        _ => (
+            "404 Not Found",
+            "text/plain",
+            "Metrics are at /metrics\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+/// Builds the metrics in the Prometheus text format. A source that fails is reported through
+/// `domeneshop_scrape_success` instead of failing the whole scrape.
+async fn collect(client: &DomeneshopClient, data_dir: &PathBuf) -> String {
+    let mut metrics = Metrics::default();
+    let today = chrono::Utc::now().date_naive();
+
+    let domains = client.list_domains().await;
+    let invoices = client
+        .list_invoices_with_status(InvoiceStatus::Unpaid)
+        .await;
+    let updates = last_updates(data_dir);
+
+    metrics.family(
+        "domeneshop_scrape_success",
+        "Whether the source could be read",
+        "gauge",
+    );
+    for (source, success) in [
+        ("domains", domains.is_ok()),
+        ("invoices", invoices.is_ok()),
+        ("dyndns_history", updates.is_ok()),
+    ] {
+        metrics.sample(
+            "domeneshop_scrape_success",
+            &[("source", source)],
+            success as u8 as f64,
+        );
+    }
+
+    match domains {
+        Ok(domains) => {
+            metrics.family(
+                "domeneshop_domain_expiry_days",
+                "Days until the domain expires",
+                "gauge",
+            );
+            for domain in &domains {
+                metrics.sample(
+                    "domeneshop_domain_expiry_days",
+                    &[("domain", &domain.domain)],
+                    (domain.expiry_date - today).num_days() as f64,
+                );
+            }
+            metrics.family(
+                "domeneshop_domain_renew",
+                "Whether the domain renews automatically",
+                "gauge",
+            );
+            for domain in &domains {
+                metrics.sample(
+                    "domeneshop_domain_renew",
+                    &[("domain", &domain.domain)],
+                    domain.renew as u8 as f64,
+                );
+            }
+        }
+        Err(err) => warn!("Could not list domains for metrics: {}", err),
+    }
+
+    match invoices {
+        Ok(invoices) => {
+            metrics.family(
+                "domeneshop_invoices_unpaid",
+                "Number of unpaid invoices",
+                "gauge",
+            );
+            metrics.sample("domeneshop_invoices_unpaid", &[], invoices.len() as f64);
+
+            metrics.family(
+                "domeneshop_invoices_unpaid_amount",
+                "Sum of unpaid invoices",
+                "gauge",
+            );
+            let mut amounts: Vec<(String, f64)> = Vec::new();
+            for invoice in &invoices {
+                let currency = invoice.currency.to_string();
+                match amounts
+                    .iter_mut()
+                    .find(|(existing, _)| *existing == currency)
+                {
+                    Some((_, amount)) => *amount += invoice.amount as f64,
+                    None => amounts.push((currency, invoice.amount as f64)),
+                }
+            }
+            for (currency, amount) in &amounts {
+                metrics.sample(
+                    "domeneshop_invoices_unpaid_amount",
+                    &[("currency", currency)],
+                    *amount,
+                );
+            }
+        }
+        Err(err) => warn!("Could not list invoices for metrics: {}", err),
+    }
+
+    match updates {
+        Ok(updates) => {
+            metrics.family(
+                "domeneshop_dyndns_last_success_timestamp_seconds",
+                "Time of the last successful dyndns update of the host",
+                "gauge",
+            );
+            for (host, timestamp) in &updates {
+                metrics.sample(
+                    "domeneshop_dyndns_last_success_timestamp_seconds",
+                    &[("host", host)],
+                    timestamp.timestamp() as f64,
+                );
+            }
+        }
+        Err(err) => warn!("Could not read dyndns history for metrics: {}", err),
+    }
+
+    metrics.family(
+        "domeneshop_dyndns_ip_info",
+        "The ip last set for the host",
+        "gauge",
+    );
+    for (host, ip) in current_ips(data_dir) {
+        let family = match ip {
+            IpAddr::V4(_) => "ipv4",
+            IpAddr::V6(_) => "ipv6",
+        };
+        metrics.sample(
+            "domeneshop_dyndns_ip_info",
+            &[("host", &host), ("family", family), ("ip", &ip.to_string())],
+            1.0,
+        );
+    }
+
+    metrics.text
+}
+
+#[derive(Default)]
+struct Metrics {
+    text: String,
+}
+
+impl Metrics {
+    fn family(&mut self, name: &str, help: &str, kind: &str) {
+        _ = writeln!(self.text, "# HELP {} {}", name, help);
+        _ = writeln!(self.text, "# TYPE {} {}", name, kind);
+    }
+
+    fn sample(&mut self, name: &str, labels: &[(&str, &str)], value: f64) {
+        let labels: Vec<String> = labels
+            .iter()
+            .map(|(label, value)| format!("{}=\"{}\"", label, escape_label(value)))
+            .collect();
+        if labels.is_empty() {
+            _ = writeln!(self.text, "{} {}", name, value);
+        } else {
+            _ = writeln!(self.text, "{}{{{}}} {}", name, labels.join(","), value);
+        }
+    }
+}
+
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,209 @@
+use std::{collections::BTreeMap, env, fs, io, path::PathBuf, process::ExitCode};
+
+use clap::{CommandFactory, Parser, ValueEnum};
+use clap_complete::{
+    engine::CompletionCandidate,
+    env::{CompleteEnv, Shells},
+};
+use domeneshop_client::endpoints::{
+    dns::ExistingDnsRecord,
+    domains::{Domain, DomainId},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config::value_name,
+    constants::{
+        APP_NAME, COMPLETION_CACHE_FILENAME, COMPLETION_ENV, DATA_DIRECTORY_ENV, DOMAIN_ENV,
+    },
+    directories::xdg_directory,
+    log_and_fail, log_and_fail_with_error, Args,
+};
+
+#[derive(Parser)]
+pub struct CompletionArgs {
+    #[arg(value_enum, help = "Shell to generate completions for")]
+    shell: Shell,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Domains and records seen by the last `domains list` and `dns list`, used to complete ids
+/// without calling the API on every key press.
+#[derive(Serialize, Deserialize, Default)]
+struct CompletionCache {
+    domains: Vec<CachedDomain>,
+    records: BTreeMap<String, Vec<CachedRecord>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedDomain {
+    id: String,
+    domain: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedRecord {
+    id: String,
+    description: String,
+}
+
+/// Answers completion requests from the registered shell script, which calls the CLI with the
+/// completion environment variable set. Does nothing otherwise.
+pub fn complete_from_env() {
+    CompleteEnv::with_factory(Args::command)
+        .var(COMPLETION_ENV)
+        .bin(APP_NAME)
+        .complete();
+}
+
+/// Prints the script that registers the completions in the shell. It calls back into the CLI, so
+/// domain and record ids can be completed as well.
+pub fn print_completions(args: &CompletionArgs) -> ExitCode {
+    let shell = value_name(&args.shell);
+    let shells = Shells::builtins();
+    let Some(completer) = shells.completer(&shell) else {
+        return log_and_fail(format!("Completions are not supported for {}", shell));
+    };
+
+    let mut stdout = io::stdout();
+    match completer.write_registration(COMPLETION_ENV, APP_NAME, APP_NAME, APP_NAME, &mut stdout) {
+        Ok(_) => ExitCode::SUCCESS,
+        Err(err) => log_and_fail_with_error("Could not write completions", err),
+    }
+}
+
+pub fn cache_domains(cache_dir: &PathBuf, domains: &[Domain]) {
+    let mut cache = read_cache(cache_dir);
+    cache.domains = domains
+        .iter()
+        .map(|domain| CachedDomain {
+            id: domain.id.to_string(),
+            domain: domain.domain.clone(),
+        })
+        .collect();
+    write_cache(cache_dir, &cache);
+}
+
+pub fn cache_dns_records(cache_dir: &PathBuf, domain_id: DomainId, records: &[ExistingDnsRecord]) {
+    let mut cache = read_cache(cache_dir);
+    cache.records.insert(
+        domain_id.to_string(),
+        records
+            .iter()
+            .map(|record| CachedRecord {
+                id: record.id.to_string(),
+                description: format!(
+                    "{} {:?} {}",
+                    record.data.host, record.data.r#type, record.data.data
+                ),
+            })
+            .collect(),
+    );
+    write_cache(cache_dir, &cache);
+}
+
+pub fn domain_id_candidates() -> Vec<CompletionCandidate> {
+    let cache = read_cache_for_completion();
+    cache
+        .domains
+        .iter()
+        .map(|domain| CompletionCandidate::new(&domain.id).help(Some(domain.domain.clone().into())))
+        .collect()
+}
+
+/// Both the names and the ids of cached domains complete `--domain`.
+pub fn domain_candidates() -> Vec<CompletionCandidate> {
+    let cache = read_cache_for_completion();
+    cache
+        .domains
+        .iter()
+        .map(|domain| CompletionCandidate::new(&domain.domain))
+        .chain(domain_id_candidates())
+        .collect()
+}
+
+/// Record ids described by host, type and data. Only records of the domain given with `--domain`
+/// or `$DOMENESHOP_DOMAIN` are offered, or those of all cached domains when neither is set.
+pub fn dns_id_candidates() -> Vec<CompletionCandidate> {
+    let cache = read_cache_for_completion();
+    let domain = domain_being_completed()
+        .or_else(|| env::var(DOMAIN_ENV).ok())
+        .filter(|domain| !domain.is_empty());
+    let matches = |domain_id: &String| match &domain {
+        None => true,
+        Some(domain) => {
+            domain_id == domain
+                || cache.domains.iter().any(|cached| {
+                    cached.id == *domain_id && cached.domain.eq_ignore_ascii_case(domain)
+                })
+        }
+    };
+
+    cache
+        .records
+        .iter()
+        .filter(|(domain_id, _)| matches(domain_id))
+        .flat_map(|(_, records)| records)
+        .map(|record| {
+            CompletionCandidate::new(&record.id).help(Some(record.description.clone().into()))
+        })
+        .collect()
+}
+
+/// Candidates don't get the other arguments, so `--domain` is found in the words being completed,
+/// which the shell passes as arguments.
+fn domain_being_completed() -> Option<String> {
+    let mut words = env::args().skip(1);
+    while let Some(word) = words.next() {
+        if word == "--domain" || word == "-d" {
+            return words.next();
+        }
+        let value = word
+            .strip_prefix("--domain=")
+            .or_else(|| word.strip_prefix("-d").filter(|_| !word.starts_with("--")));
+        if let Some(value) = value {
+            return Some(value.trim_start_matches('=').to_string());
+        }
+    }
+    None
+}
+
+/// Completion runs before arguments are parsed, so the cache is found from the environment
+/// alone.
+fn read_cache_for_completion() -> CompletionCache {
+    let cache_dir = env::var_os(DATA_DIRECTORY_ENV)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| xdg_directory("XDG_CACHE_HOME", ".cache"));
+    match cache_dir {
+        Some(cache_dir) => read_cache(&cache_dir),
+        None => CompletionCache::default(),
+    }
+}
+
+fn read_cache(cache_dir: &PathBuf) -> CompletionCache {
+    fs::read_to_string(cache_dir.join(COMPLETION_CACHE_FILENAME))
+        .ok()
+        .and_then(|data| serde_json::from_str(&data).ok())
+        .unwrap_or_default()
+}
+
+fn write_cache(cache_dir: &PathBuf, cache: &CompletionCache) {
+    let file = cache_dir.join(COMPLETION_CACHE_FILENAME);
+    let result = serde_json::to_string(cache)
+        .map_err(|err| err.to_string())
+        .and_then(|data| fs::write(&file, data).map_err(|err| err.to_string()));
+    if let Err(err) = result {
+        warn!(
+            "Could not update completion cache {}: {}",
+            file.display(),
+            err
+        );
+    }
+}
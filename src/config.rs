@@ -0,0 +1,297 @@
+use std::{
+    env,
+    fmt::{self, Display},
+    fs,
+    path::PathBuf,
+};
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use simple_log::log_level;
+
+use crate::{
+    constants::{
+        CONFIG_ENV, CONFIG_FILENAME, DATA_DIRECTORY_ENV, DOMAIN_ENV, DYNDNS_HOSTS_ENV,
+        LOG_DIRECTORY_ENV, LOG_LEVEL_ENV, OUTPUT_ENV, PROFILE_ENV,
+    },
+    directories::xdg_directory,
+    domain_lookup::DomainIdOrHost,
+    output::OutputFormat,
+    GlobalOpts,
+};
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Trace => log_level::TRACE,
+            LogLevel::Debug => log_level::DEBUG,
+            LogLevel::Info => log_level::INFO,
+            LogLevel::Warn => log_level::WARN,
+            LogLevel::Error => log_level::ERROR,
+        }
+    }
+}
+
+/// Contents of `config.toml`. Values are kept as strings and parsed the same way as environment
+/// variables, so both give the same errors.
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct ConfigFile {
+    domain: Option<String>,
+    output: Option<String>,
+    profile: Option<String>,
+    log_level: Option<String>,
+    data_directory: Option<String>,
+    log_directory: Option<String>,
+    #[serde(default)]
+    dyndns: DyndnsConfig,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(deny_unknown_fields)]
+struct DyndnsConfig {
+    hosts: Option<Vec<String>>,
+}
+
+/// Where the effective value of a setting came from.
+#[derive(Clone)]
+pub enum Origin {
+    Default,
+    Config(PathBuf),
+    Environment(&'static str),
+    Flag,
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Default => write!(f, "default"),
+            Origin::Config(file) => write!(f, "{}", file.display()),
+            Origin::Environment(variable) => write!(f, "${}", variable),
+            Origin::Flag => write!(f, "command line"),
+        }
+    }
+}
+
+pub struct Setting<T> {
+    pub value: T,
+    pub origin: Origin,
+}
+
+/// Settings shared by all commands. Each value is taken from the first of the flag, the
+/// environment variable and `config.toml` that has it, and falls back to a default.
+pub struct Settings {
+    pub file: Setting<PathBuf>,
+    pub data_directory: Option<Setting<PathBuf>>,
+    pub log_directory: Option<Setting<PathBuf>>,
+    pub profile: Option<Setting<String>>,
+    pub output: Setting<OutputFormat>,
+    pub log_level: Setting<LogLevel>,
+    pub domain: Option<Setting<DomainIdOrHost>>,
+    pub dyndns_hosts: Option<Setting<Vec<String>>>,
+}
+
+impl Settings {
+    /// Reads `config.toml` and layers the environment and flags on top. This runs before logging
+    /// is initialized, so nothing is logged.
+    pub fn load(opts: &GlobalOpts) -> Result<Settings, String> {
+        let file = config_file(opts)?;
+        let config = read_config(&file)?;
+        let path = &file.value.clone();
+
+        Ok(Settings {
+            data_directory: layer(
+                opts.data_directory.as_ref().map(PathBuf::from),
+                DATA_DIRECTORY_ENV,
+                config.data_directory,
+                path,
+                parse_path,
+            )?,
+            log_directory: layer(
+                opts.log_directory.as_ref().map(PathBuf::from),
+                LOG_DIRECTORY_ENV,
+                config.log_directory,
+                path,
+                parse_path,
+            )?,
+            profile: layer(
+                opts.profile.clone(),
+                PROFILE_ENV,
+                config.profile,
+                path,
+                |profile| Ok(profile.to_string()),
+            )?,
+            output: layer(
+                opts.output,
+                OUTPUT_ENV,
+                config.output,
+                path,
+                parse_value_enum,
+            )?
+            .unwrap_or(default_setting()),
+            log_level: layer(
+                opts.debug.then_some(LogLevel::Debug).or(opts.log_level),
+                LOG_LEVEL_ENV,
+                config.log_level,
+                path,
+                parse_value_enum,
+            )?
+            .unwrap_or(default_setting()),
+            domain: layer(None, DOMAIN_ENV, config.domain, path, |domain| {
+                domain
+                    .parse::<DomainIdOrHost>()
+                    .map_err(|err| err.to_string())
+            })?,
+            dyndns_hosts: layer_hosts(config.dyndns.hosts, path),
+            file,
+        })
+    }
+}
+
+/// The file is `--config`, `$DOMENESHOP_CONFIG`, or `config.toml` in the config directory. The
+/// data directory can't come from the file itself, so only the flag and variable are used here.
+fn config_file(opts: &GlobalOpts) -> Result<Setting<PathBuf>, String> {
+    if let Some(file) = &opts.config {
+        return Ok(Setting {
+            value: PathBuf::from(file),
+            origin: Origin::Flag,
+        });
+    }
+    if let Some(file) = env::var_os(CONFIG_ENV).filter(|file| !file.is_empty()) {
+        return Ok(Setting {
+            value: PathBuf::from(file),
+            origin: Origin::Environment(CONFIG_ENV),
+        });
+    }
+
+    let data_directory = opts.data_directory.clone().or_else(|| {
+        env::var(DATA_DIRECTORY_ENV)
+            .ok()
+            .filter(|dir| !dir.is_empty())
+    });
+    let config_dir = match data_directory {
+        Some(dir) => PathBuf::from(dir),
+        None => xdg_directory("XDG_CONFIG_HOME", ".config")
+            .ok_or("Could not find the config directory".to_string())?,
+    };
+    Ok(Setting {
+        value: config_dir.join(CONFIG_FILENAME),
+        origin: Origin::Default,
+    })
+}
+
+/// A missing file is only an error when it was asked for explicitly.
+fn read_config(file: &Setting<PathBuf>) -> Result<ConfigFile, String> {
+    match fs::read_to_string(&file.value) {
+        Ok(data) => toml::from_str(&data)
+            .map_err(|err| format!("Could not parse {}: {}", file.value.display(), err)),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => match file.origin {
+            Origin::Default => Ok(ConfigFile::default()),
+            _ => Err(format!(
+                "Config file {} does not exist",
+                file.value.display()
+            )),
+        },
+        Err(err) => Err(format!("Could not read {}: {}", file.value.display(), err)),
+    }
+}
+
+fn layer<T>(
+    flag: Option<T>,
+    variable: &'static str,
+    configured: Option<String>,
+    file: &PathBuf,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<Option<Setting<T>>, String> {
+    if let Some(value) = flag {
+        return Ok(Some(Setting {
+            value,
+            origin: Origin::Flag,
+        }));
+    }
+
+    if let Some(value) = env::var(variable).ok().filter(|value| !value.is_empty()) {
+        return parse(&value)
+            .map(|value| {
+                Some(Setting {
+                    value,
+                    origin: Origin::Environment(variable),
+                })
+            })
+            .map_err(|err| format!("Invalid ${}: {}", variable, err));
+    }
+
+    match configured {
+        None => Ok(None),
+        Some(value) => parse(&value)
+            .map(|value| {
+                Some(Setting {
+                    value,
+                    origin: Origin::Config(file.clone()),
+                })
+            })
+            .map_err(|err| format!("Invalid value in {}: {}", file.display(), err)),
+    }
+}
+
+/// Hosts given as arguments are handled by the dyndns command itself, as they replace these.
+fn layer_hosts(configured: Option<Vec<String>>, file: &PathBuf) -> Option<Setting<Vec<String>>> {
+    match env::var(DYNDNS_HOSTS_ENV)
+        .ok()
+        .filter(|hosts| !hosts.is_empty())
+    {
+        Some(hosts) => Some(Setting {
+            value: hosts
+                .split(',')
+                .map(str::trim)
+                .filter(|host| !host.is_empty())
+                .map(String::from)
+                .collect(),
+            origin: Origin::Environment(DYNDNS_HOSTS_ENV),
+        }),
+        None => configured.map(|hosts| Setting {
+            value: hosts,
+            origin: Origin::Config(file.clone()),
+        }),
+    }
+}
+
+fn default_setting<T: Default>() -> Setting<T> {
+    Setting {
+        value: T::default(),
+        origin: Origin::Default,
+    }
+}
+
+fn parse_path(path: &str) -> Result<PathBuf, String> {
+    Ok(PathBuf::from(path))
+}
+
+fn parse_value_enum<T: ValueEnum>(value: &str) -> Result<T, String> {
+    T::from_str(value, true).map_err(|_| {
+        let allowed: Vec<String> = T::value_variants()
+            .iter()
+            .filter_map(|variant| variant.to_possible_value())
+            .map(|value| value.get_name().to_string())
+            .collect();
+        format!("'{}' is not one of {}", value, allowed.join(", "))
+    })
+}
+
+/// Name of an enum value as it is written on the command line.
+pub fn value_name<T: ValueEnum>(value: &T) -> String {
+    value
+        .to_possible_value()
+        .map(|value| value.get_name().to_string())
+        .unwrap_or_default()
+}
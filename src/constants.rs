@@ -1,3 +1,25 @@
+pub const APP_NAME: &str = "domeneshop_cli";
 pub const CREDENTIALS_FILENAME: &str = "credentials.json";
-pub const LAST_IP_FILENAME: &str = "last_ip";
-pub const DYNDNS_EXECUTION_LOG_FILENAME: &str = "dyndns_log";
+pub const CREDENTIALS_PROFILES_FILENAME: &str = "credentials.toml";
+pub const DEFAULT_PROFILE: &str = "default";
+pub const PROFILE_ENV: &str = "DOMENESHOP_PROFILE";
+pub const CONFIG_FILENAME: &str = "config.toml";
+pub const CONFIG_ENV: &str = "DOMENESHOP_CONFIG";
+pub const DATA_DIRECTORY_ENV: &str = "DOMENESHOP_DATA_DIRECTORY";
+pub const LOG_DIRECTORY_ENV: &str = "DOMENESHOP_LOG_DIRECTORY";
+pub const OUTPUT_ENV: &str = "DOMENESHOP_OUTPUT";
+pub const LOG_LEVEL_ENV: &str = "DOMENESHOP_LOG_LEVEL";
+pub const DOMAIN_ENV: &str = "DOMENESHOP_DOMAIN";
+pub const DYNDNS_HOSTS_ENV: &str = "DOMENESHOP_DYNDNS_HOSTS";
+pub const TOKEN_ENV: &str = "DOMENESHOP_TOKEN";
+pub const SECRET_ENV: &str = "DOMENESHOP_SECRET";
+pub const KEYRING_SERVICE: &str = "domeneshop_cli";
+pub const LAST_IPV4_FILENAME: &str = "last_ipv4";
+pub const LAST_IPV6_FILENAME: &str = "last_ipv6";
+pub const DYNDNS_HISTORY_FILENAME: &str = "dyndns_history.jsonl";
+// Written to the current directory by earlier versions
+pub const LEGACY_LAST_IP_FILENAME: &str = "last_ip";
+pub const LEGACY_DYNDNS_LOG_FILENAME: &str = "dyndns_log";
+pub const DEFAULT_DNS_TTL: i32 = 3600;
+pub const COMPLETION_CACHE_FILENAME: &str = "completion_cache.json";
+pub const COMPLETION_ENV: &str = "DOMENESHOP_COMPLETE";
@@ -0,0 +1,134 @@
+use std::{
+    env, fs,
+    net::IpAddr,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    config::Settings,
+    constants::{
+        CREDENTIALS_FILENAME, DYNDNS_HISTORY_FILENAME, LAST_IPV4_FILENAME, LAST_IPV6_FILENAME,
+        LEGACY_DYNDNS_LOG_FILENAME, LEGACY_LAST_IP_FILENAME,
+    },
+};
+
+const APP_DIRECTORY: &str = "domeneshop";
+
+/// Where the CLI keeps its files. Defaults follow the XDG Base Directory specification, and
+/// `--data-directory` puts everything but the logs in a single directory.
+pub struct Directories {
+    /// Credentials and configuration
+    pub config: PathBuf,
+    /// Data that should survive between runs, such as the last dyndns ip and its history
+    pub state: PathBuf,
+    /// Data that can be recreated, such as values used for shell completion
+    pub cache: PathBuf,
+    pub log: PathBuf,
+}
+
+/// Resolves and creates the directories. This runs before logging is initialized, so errors are
+/// only printed.
+pub fn get_directories(settings: &Settings) -> Option<Directories> {
+    let log_directory = settings
+        .log_directory
+        .as_ref()
+        .map(|setting| setting.value.clone());
+    let directories = match &settings.data_directory {
+        Some(dir) => {
+            let dir = dir.value.clone();
+            Directories {
+                config: dir.clone(),
+                state: dir.clone(),
+                cache: dir.clone(),
+                log: log_directory.unwrap_or(dir),
+            }
+        }
+        None => {
+            let state = xdg_directory("XDG_STATE_HOME", ".local/state")?;
+            Directories {
+                config: xdg_directory("XDG_CONFIG_HOME", ".config")?,
+                cache: xdg_directory("XDG_CACHE_HOME", ".cache")?,
+                log: log_directory.unwrap_or(state.clone()),
+                state,
+            }
+        }
+    };
+
+    for dir in [
+        &directories.config,
+        &directories.state,
+        &directories.cache,
+        &directories.log,
+    ] {
+        if let Err(err) = fs::create_dir_all(dir) {
+            eprintln!("Could not create directory {}: {}", dir.display(), err);
+            return None;
+        }
+    }
+
+    if settings.data_directory.is_none() {
+        print_migration_hints(&directories);
+    }
+    Some(directories)
+}
+
+/// Earlier versions kept their files in the current directory. Files left there are no longer
+/// read, so point out where they belong instead of acting as if there were none.
+fn print_migration_hints(directories: &Directories) {
+    if Path::new(CREDENTIALS_FILENAME).is_file()
+        && !directories.config.join(CREDENTIALS_FILENAME).exists()
+    {
+        eprintln!(
+            "Found {} in the current directory, which is no longer read. Move it with \
+             `mv {} {}`, or pass `--data-directory .` to keep using it",
+            CREDENTIALS_FILENAME,
+            CREDENTIALS_FILENAME,
+            directories.config.display()
+        );
+    }
+
+    if let Ok(ip) = fs::read_to_string(LEGACY_LAST_IP_FILENAME) {
+        let filename = match ip.trim().parse::<IpAddr>() {
+            Ok(IpAddr::V6(_)) => LAST_IPV6_FILENAME,
+            _ => LAST_IPV4_FILENAME,
+        };
+        eprintln!(
+            "Found {} in the current directory, which is no longer read. The last dyndns ip is \
+             now kept per host, so copy it to {} for each host, then remove it",
+            LEGACY_LAST_IP_FILENAME,
+            directories
+                .state
+                .join(format!("{}.<host>", filename))
+                .display()
+        );
+    }
+
+    if Path::new(LEGACY_DYNDNS_LOG_FILENAME).is_file() {
+        eprintln!(
+            "Found {} in the current directory, which is no longer written. Updates are now \
+             recorded in {} and shown by `dyndns history`, so it can be archived or removed",
+            LEGACY_DYNDNS_LOG_FILENAME,
+            directories.state.join(DYNDNS_HISTORY_FILENAME).display()
+        );
+    }
+}
+
+/// Uses the XDG variable when it holds an absolute path, as the specification requires, and the
+/// default below the home directory otherwise.
+pub fn xdg_directory(variable: &str, default: &str) -> Option<PathBuf> {
+    let base = env::var_os(variable)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(default)));
+
+    match base {
+        Some(base) => Some(base.join(APP_DIRECTORY)),
+        None => {
+            eprintln!(
+                "Could not resolve a directory from ${} or $HOME, use --data-directory",
+                variable
+            );
+            None
+        }
+    }
+}
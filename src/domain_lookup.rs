@@ -1,4 +1,7 @@
-use std::str::FromStr;
+use std::{
+    fmt::{self, Display},
+    str::FromStr,
+};
 
 use domeneshop_client::{client::DomeneshopClient, endpoints::domains::DomainId};
 
@@ -18,6 +21,15 @@ impl FromStr for DomainIdOrHost {
     }
 }
 
+impl Display for DomainIdOrHost {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DomainIdOrHost::DomainId(id) => write!(f, "{}", id),
+            DomainIdOrHost::Host(host) => write!(f, "{}", host),
+        }
+    }
+}
+
 pub async fn get_domain_id(
     domain_input: &DomainIdOrHost,
     client: &DomeneshopClient,
@@ -36,3 +48,34 @@ pub async fn get_domain_id(
         },
     }
 }
+
+/// Finds the domain a hostname like `home.example.com` belongs to, along with the host part of
+/// the hostname relative to that domain (`home`, or `@` for the domain itself).
+pub async fn find_domain_for_hostname(
+    hostname: &str,
+    client: &DomeneshopClient,
+) -> Option<(DomainId, String)> {
+    let hostname = hostname.trim_end_matches('.').to_lowercase();
+    let domains = match client.list_domains().await {
+        Ok(domains) => domains,
+        Err(err) => {
+            warn!("Error while fetching domains: {}", err);
+            return None;
+        }
+    };
+
+    domains
+        .iter()
+        .filter_map(|domain| {
+            let name = domain.domain.to_lowercase();
+            if hostname == name {
+                Some((domain.id, "@".to_string(), name.len()))
+            } else {
+                hostname
+                    .strip_suffix(&format!(".{}", name))
+                    .map(|host| (domain.id, host.to_string(), name.len()))
+            }
+        })
+        .max_by_key(|(_, _, length)| *length)
+        .map(|(id, host, _)| (id, host))
+}
@@ -0,0 +1,26 @@
+use std::time::Duration;
+
+/// Parses durations like `30s`, `5m`, `12h` or `30d`. A plain number is read as seconds.
+pub fn parse_duration(input: &str) -> Result<Duration, String> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (value, unit) = input.split_at(split);
+
+    let value: u64 = value
+        .parse()
+        .map_err(|_| format!("'{}' is not a valid duration", input))?;
+    let seconds = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("Unknown unit '{}', use s, m, h or d", unit)),
+    };
+
+    value
+        .checked_mul(seconds)
+        .map(Duration::from_secs)
+        .ok_or(format!("'{}' is too long", input))
+}
@@ -2,37 +2,59 @@
 extern crate simple_log;
 
 mod client;
+mod completion;
+mod config;
 mod constants;
+mod directories;
 pub mod domain_lookup;
+mod duration;
+mod output;
+mod prompt;
+mod shutdown;
 mod commands {
+    pub mod auth;
+    pub mod config;
     pub mod dns;
     pub mod domain;
     pub mod dyndns;
     pub mod forwards;
     pub mod invoices;
+    pub mod metrics;
 }
 
 use client::get_client;
+use commands::auth::handle_auth;
+use commands::config::handle_config;
 use commands::dns::handle_dns;
 use commands::domain::handle_domains;
-use commands::dyndns::handle_dyndns;
+use commands::dyndns::{handle_dyndns, handle_dyndns_history};
 use commands::forwards::handle_forwards;
 use commands::invoices::handle_invoices;
+use commands::metrics::handle_metrics;
+use completion::{complete_from_env, print_completions};
+use config::{LogLevel, Settings};
+use directories::{get_directories, Directories};
 use domeneshop_client::client::DomeneshopClient;
-use simple_log::{log_level, LogConfigBuilder, SimpleResult};
+use output::{Output, OutputFormat};
+use simple_log::{LogConfigBuilder, SimpleResult};
 use std::fmt::{Debug, Display};
 use std::process::ExitCode;
-use std::{env, path::PathBuf};
 
-use clap::{arg, Parser};
+use clap::Parser;
 
 #[derive(Parser)]
 pub enum Command {
+    Auth(commands::auth::AuthArgs),
+    Config(commands::config::ConfigArgs),
+    /// Prints shell completions. Add `source <(domeneshop_cli completions bash)` to ~/.bashrc
+    Completions(completion::CompletionArgs),
     Dyndns(commands::dyndns::Command),
     Domains(commands::domain::DomainArgs),
     Invoices(commands::invoices::InvoiceArgs),
     Dns(commands::dns::DnsArgs),
     Forwards(commands::forwards::ForwardArgs),
+    /// Exports domain, invoice and dyndns metrics for Prometheus
+    Metrics(commands::metrics::MetricsArgs),
 }
 
 #[derive(Parser)]
@@ -49,6 +71,25 @@ pub struct GlobalOpts {
     token: Option<String>,
     #[arg(long, global = true, help = "Domeneshop API secret")]
     secret: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        help = "Credential profile to use from credentials.toml. Can also be set with DOMENESHOP_PROFILE"
+    )]
+    profile: Option<String>,
+    #[arg(
+        long,
+        action,
+        global = true,
+        help = "Refuses to read credentials files that other users can access"
+    )]
+    strict: bool,
+    #[arg(
+        long,
+        global = true,
+        help = "Config file to read. Defaults to config.toml in the config directory"
+    )]
+    config: Option<String>,
     #[arg(
         long,
         action,
@@ -56,67 +97,132 @@ pub struct GlobalOpts {
         help = "Enables expanded logging and outputs logs to console"
     )]
     debug: bool,
+    #[arg(long, global = true, value_enum, help = "Level of messages to log")]
+    log_level: Option<LogLevel>,
     #[arg(
         long,
         global = true,
-        help = "Directory to store CLI data. Defaults to current directory"
+        help = "Directory to store credentials, configuration and state. Defaults to the XDG config, state and cache directories"
     )]
     data_directory: Option<String>,
     #[arg(
         long,
         global = true,
-        help = "Directory to store logs. Defaults to current directory"
+        help = "Directory to store logs. Defaults to the data directory, or the XDG state directory"
     )]
     log_directory: Option<String>,
+    #[arg(
+        long,
+        global = true,
+        value_enum,
+        help = "Format of the printed results [default: table]"
+    )]
+    output: Option<OutputFormat>,
+    #[arg(
+        long,
+        global = true,
+        value_delimiter = ',',
+        help = "Comma separated list of fields to print, e.g. host,type,data"
+    )]
+    columns: Option<Vec<String>>,
 }
 
 #[tokio::main]
 async fn main() -> ExitCode {
+    complete_from_env();
     let args = Args::parse();
+    if let Command::Completions(command) = &args.command {
+        return print_completions(command);
+    }
+
+    let settings = match Settings::load(&args.global_opts) {
+        Ok(settings) => settings,
+        Err(err) => {
+            eprintln!("{}", err);
+            return ExitCode::FAILURE;
+        }
+    };
+    let Some(directories) = get_directories(&settings) else {
+        return ExitCode::FAILURE;
+    };
 
-    match initialize_logging(&args) {
+    match initialize_logging(&args, &settings, &directories) {
         Err(err) => {
             eprintln!("Could not initialize logging: {}", err);
             ExitCode::FAILURE
         }
-        Ok(_) => match get_data_directory(&args) {
-            None => ExitCode::FAILURE,
-            Some(data_dir) => match get_client(&args, &data_dir) {
+        Ok(_) => {
+            debug!(
+                "Using config directory {}, state directory {} and cache directory {}",
+                directories.config.display(),
+                directories.state.display(),
+                directories.cache.display()
+            );
+            let output = Output::new(settings.output.value, args.global_opts.columns.clone());
+            match &args.command {
+                Command::Auth(command) => {
+                    return handle_auth(command, &args, &settings, &directories.config).await
+                }
+                Command::Config(command) => return handle_config(command, &settings, &output),
+                Command::Dyndns(command) => {
+                    if let Some(code) = handle_dyndns_history(command, &directories.state, &output)
+                    {
+                        return code;
+                    }
+                }
+                _ => (),
+            }
+            match get_client(&args, &settings, &directories.config) {
                 None => ExitCode::FAILURE,
-                Some(client) => run_command(&client, &args, &data_dir).await,
-            },
-        },
+                Some(client) => run_command(&client, &args, &settings, &directories, &output).await,
+            }
+        }
     }
 }
 
-async fn run_command(client: &DomeneshopClient, args: &Args, data_dir: &PathBuf) -> ExitCode {
+async fn run_command(
+    client: &DomeneshopClient,
+    args: &Args,
+    settings: &Settings,
+    directories: &Directories,
+    output: &Output,
+) -> ExitCode {
+    let domain = settings.domain.as_ref().map(|domain| &domain.value);
+    let dyndns_hosts = settings
+        .dyndns_hosts
+        .as_ref()
+        .map(|hosts| hosts.value.as_slice())
+        .unwrap_or_default();
     match &args.command {
-        Command::Dyndns(command) => handle_dyndns(command, client, data_dir).await,
-        Command::Domains(command) => handle_domains(command, client).await,
-        Command::Invoices(command) => handle_invoices(command, client).await,
-        Command::Dns(command) => handle_dns(command, client).await,
-        Command::Forwards(command) => handle_forwards(command, client).await,
+        Command::Auth(_) | Command::Config(_) | Command::Completions(_) => {
+            unreachable!("runs without a client")
+        }
+        Command::Dyndns(command) => {
+            handle_dyndns(command, client, dyndns_hosts, &directories.state).await
+        }
+        Command::Domains(command) => {
+            handle_domains(command, client, &directories.cache, output).await
+        }
+        Command::Invoices(command) => handle_invoices(command, client, output).await,
+        Command::Dns(command) => {
+            handle_dns(command, client, domain, &directories.cache, output).await
+        }
+        Command::Forwards(command) => handle_forwards(command, client, domain, output).await,
+        Command::Metrics(command) => handle_metrics(command, client, &directories.state).await,
     }
 }
 
-fn initialize_logging(args: &Args) -> SimpleResult<()> {
-    // TODO Change to follow XDG-spec and handle error from current_dir
-
-    let log_dir = args
-        .global_opts
-        .log_directory
-        .clone()
-        .unwrap_or_else(|| env::current_dir().unwrap().to_string_lossy().to_string());
-    let level = if args.global_opts.debug {
-        log_level::DEBUG
-    } else {
-        log_level::INFO
-    };
+fn initialize_logging(
+    args: &Args,
+    settings: &Settings,
+    directories: &Directories,
+) -> SimpleResult<()> {
+    let level = settings.log_level.value.as_str();
 
-    let log_file = format!("{}/domeneshop_cli.log", log_dir);
+    let log_file = directories.log.join("domeneshop_cli.log");
 
     let mut configbuilder = LogConfigBuilder::builder()
-        .path(&log_file)
+        .path(log_file.to_string_lossy())
         .size(1 * 100)
         .roll_count(10)
         .level(level)
@@ -132,19 +238,6 @@ fn initialize_logging(args: &Args) -> SimpleResult<()> {
     SimpleResult::Ok(())
 }
 
-fn get_data_directory(args: &Args) -> Option<PathBuf> {
-    if let Some(dir) = &args.global_opts.data_directory {
-        debug!("Using data directory from arguments");
-        Some(PathBuf::from(dir))
-    } else if let Ok(current_dir) = env::current_dir() {
-        debug!("Using current dir as data directory");
-        Some(current_dir)
-    } else {
-        warn!("Could not resolve data directory");
-        None
-    }
-}
-
 pub fn log_and_fail<S>(text: S) -> ExitCode
 where
     S: Into<String> + Display,
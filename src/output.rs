@@ -0,0 +1,264 @@
+use std::process::ExitCode;
+
+use clap::ValueEnum;
+use serde_json::{Map, Value};
+
+use crate::log_and_fail;
+
+const COLUMN_SEPARATOR: &str = "  ";
+const MIN_COLUMN_WIDTH: usize = 6;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+    Yaml,
+    Csv,
+}
+
+/// Implemented by everything the CLI prints, so all commands share one rendering of each format.
+pub trait Render {
+    /// Columns shown when listing in the table format without `--columns`.
+    const DEFAULT_COLUMNS: &'static [&'static str];
+
+    fn fields(&self) -> Vec<(&'static str, Value)>;
+}
+
+type Row<'a> = Vec<(&'a str, Value)>;
+
+pub struct Output {
+    format: OutputFormat,
+    columns: Option<Vec<String>>,
+}
+
+impl Output {
+    pub fn new(format: OutputFormat, columns: Option<Vec<String>>) -> Output {
+        Output { format, columns }
+    }
+
+    /// Fails without printing anything when `--columns` names a field the items don't have.
+    pub fn list<T: Render>(&self, items: &[T]) -> ExitCode {
+        if let Some(Err(err)) = items.first().map(|item| self.check_columns(item)) {
+            return log_and_fail(err);
+        }
+        let columns: Option<Vec<&str>> = match (&self.columns, self.format) {
+            (Some(columns), _) => Some(columns.iter().map(String::as_str).collect()),
+            (None, OutputFormat::Table) => Some(T::DEFAULT_COLUMNS.to_vec()),
+            (None, _) => None,
+        };
+        let rows: Vec<Row> = items
+            .iter()
+            .map(|item| select(item.fields(), columns.as_deref()))
+            .collect();
+
+        match self.format {
+            OutputFormat::Json => {
+                print_json(&Value::Array(rows.into_iter().map(to_object).collect()))
+            }
+            OutputFormat::Yaml => {
+                print_yaml(&Value::Array(rows.into_iter().map(to_object).collect()))
+            }
+            OutputFormat::Csv => print_csv(&header(&rows, columns.as_deref()), &rows),
+            OutputFormat::Table => print_table(&header(&rows, columns.as_deref()), &rows),
+        }
+        ExitCode::SUCCESS
+    }
+
+    pub fn item<T: Render>(&self, item: &T) -> ExitCode {
+        if let Err(err) = self.check_columns(item) {
+            return log_and_fail(err);
+        }
+        let columns: Option<Vec<&str>> = self
+            .columns
+            .as_ref()
+            .map(|columns| columns.iter().map(String::as_str).collect());
+        let fields = select(item.fields(), columns.as_deref());
+
+        match self.format {
+            OutputFormat::Json => print_json(&to_object(fields)),
+            OutputFormat::Yaml => print_yaml(&to_object(fields)),
+            OutputFormat::Csv => {
+                let rows = [fields];
+                print_csv(&header(&rows, None), &rows)
+            }
+            OutputFormat::Table => {
+                for (name, value) in fields {
+                    println!("{}: {}", name, plain(&value));
+                }
+            }
+        }
+        ExitCode::SUCCESS
+    }
+
+    fn check_columns<T: Render>(&self, item: &T) -> Result<(), String> {
+        let Some(columns) = &self.columns else {
+            return Ok(());
+        };
+        let fields: Vec<&str> = item.fields().into_iter().map(|(name, _)| name).collect();
+        let unknown: Vec<&str> = columns
+            .iter()
+            .map(String::as_str)
+            .filter(|column| !fields.contains(column))
+            .collect();
+
+        if unknown.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Unknown columns: {}. Available columns are {}",
+                unknown.join(", "),
+                fields.join(", ")
+            ))
+        }
+    }
+}
+
+/// Picks the given columns in the given order. The columns have been checked against the fields,
+/// so a missing value only comes from an item that leaves a field out.
+fn select<'a>(fields: Row<'static>, columns: Option<&[&'a str]>) -> Row<'a> {
+    match columns {
+        None => fields,
+        Some(columns) => columns
+            .iter()
+            .map(|column| {
+                let value = fields
+                    .iter()
+                    .find(|(name, _)| name == column)
+                    .map(|(_, value)| value.clone())
+                    .unwrap_or(Value::Null);
+                (*column, value)
+            })
+            .collect(),
+    }
+}
+
+fn header<'a>(rows: &[Row<'a>], columns: Option<&[&'a str]>) -> Vec<&'a str> {
+    match (columns, rows.first()) {
+        (Some(columns), _) => columns.to_vec(),
+        (None, Some(first)) => first.iter().map(|(name, _)| *name).collect(),
+        (None, None) => Vec::new(),
+    }
+}
+
+fn to_object(fields: Row) -> Value {
+    Value::Object(
+        fields
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), value))
+            .collect::<Map<String, Value>>(),
+    )
+}
+
+fn print_json(value: &Value) {
+    match serde_json::to_string_pretty(value) {
+        Ok(json) => println!("{}", json),
+        Err(err) => error!("Could not serialize output: {}", err),
+    }
+}
+
+fn print_yaml(value: &Value) {
+    match serde_yaml::to_string(value) {
+        Ok(yaml) => print!("{}", yaml),
+        Err(err) => error!("Could not serialize output: {}", err),
+    }
+}
+
+fn print_csv(header: &[&str], rows: &[Row]) {
+    if header.is_empty() {
+        return;
+    }
+    let header: Vec<String> = header.iter().map(|name| csv_escape(name)).collect();
+    println!("{}", header.join(","));
+    for row in rows {
+        let values: Vec<String> = row
+            .iter()
+            .map(|(_, value)| csv_escape(&plain(value)))
+            .collect();
+        println!("{}", values.join(","));
+    }
+}
+
+fn print_table(header: &[&str], rows: &[Row]) {
+    if header.is_empty() {
+        return;
+    }
+    let header: Vec<String> = header.iter().map(|name| name.to_uppercase()).collect();
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| row.iter().map(|(_, value)| plain(value)).collect())
+        .collect();
+
+    let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    if let Some((terminal_size::Width(max), _)) = terminal_size::terminal_size() {
+        fit_widths(&mut widths, max as usize);
+    }
+
+    print_row(&header, &widths);
+    for row in &cells {
+        print_row(row, &widths);
+    }
+}
+
+/// Shrinks the widest columns one character at a time until the table fits, without going below
+/// a width where the content stops being recognizable.
+fn fit_widths(widths: &mut [usize], max: usize) {
+    let separators = COLUMN_SEPARATOR.len() * widths.len().saturating_sub(1);
+    while widths.iter().sum::<usize>() + separators > max {
+        match widths.iter_mut().max() {
+            Some(widest) if *widest > MIN_COLUMN_WIDTH => *widest -= 1,
+            _ => return,
+        }
+    }
+}
+
+fn print_row(cells: &[String], widths: &[usize]) {
+    let last = cells.len().saturating_sub(1);
+    let line: Vec<String> = cells
+        .iter()
+        .zip(widths)
+        .enumerate()
+        .map(|(index, (cell, width))| {
+            let cell = truncate(cell, *width);
+            if index == last {
+                cell
+            } else {
+                format!("{:<width$}", cell, width = width)
+            }
+        })
+        .collect();
+    println!("{}", line.join(COLUMN_SEPARATOR));
+}
+
+fn truncate(value: &str, width: usize) -> String {
+    if value.chars().count() <= width {
+        value.to_string()
+    } else {
+        let mut truncated: String = value.chars().take(width.saturating_sub(1)).collect();
+        truncated.push('…');
+        truncated
+    }
+}
+
+/// Formats a value for the text based formats, leaving out the quotes json would add to strings.
+fn plain(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(value) => value.clone(),
+        Value::Array(values) => values.iter().map(plain).collect::<Vec<String>>().join(", "),
+        value => value.to_string(),
+    }
+}
+
+fn csv_escape(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
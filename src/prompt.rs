@@ -0,0 +1,23 @@
+use std::io::{self, IsTerminal, Write};
+
+/// Questions can only be answered when stdin is a terminal. Otherwise, such as in scripts and
+/// pipes, commands should ask for `--yes` instead of reading an answer that never comes.
+pub fn can_prompt() -> bool {
+    io::stdin().is_terminal()
+}
+
+pub fn confirm(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    if let Err(err) = io::stdout().flush() {
+        warn!("Could not flush stdout: {}", err);
+    }
+
+    let mut answer = String::new();
+    match io::stdin().read_line(&mut answer) {
+        Ok(_) => matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"),
+        Err(err) => {
+            warn!("Could not read answer: {}", err);
+            false
+        }
+    }
+}
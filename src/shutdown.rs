@@ -0,0 +1,36 @@
+/// Signal handlers are registered up front, so Ctrl-C or SIGTERM received while no one waits,
+/// such as during an update, is still seen by the next `wait`.
+pub struct Shutdown {
+    #[cfg(unix)]
+    interrupt: tokio::signal::unix::Signal,
+    #[cfg(unix)]
+    terminate: tokio::signal::unix::Signal,
+    #[cfg(windows)]
+    ctrl_c: tokio::signal::windows::CtrlC,
+}
+
+impl Shutdown {
+    pub fn listen() -> std::io::Result<Shutdown> {
+        #[cfg(unix)]
+        use tokio::signal::unix::{signal, SignalKind};
+
+        Ok(Shutdown {
+            #[cfg(unix)]
+            interrupt: signal(SignalKind::interrupt())?,
+            #[cfg(unix)]
+            terminate: signal(SignalKind::terminate())?,
+            #[cfg(windows)]
+            ctrl_c: tokio::signal::windows::ctrl_c()?,
+        })
+    }
+
+    pub async fn wait(&mut self) {
+        #[cfg(unix)]
+        tokio::select! {
+            _ = self.interrupt.recv() => (),
+            _ = self.terminate.recv() => (),
+        }
+        #[cfg(windows)]
+        self.ctrl_c.recv().await;
+    }
+}